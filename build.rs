@@ -316,4 +316,40 @@ fn gen_verb_bindings(ver: VerbsVersion, include_dirs: Vec<String>) {
     bindings
         .write_to_file(dest)
         .expect("failed to write bindings");
+
+    if env::var_os("CARGO_FEATURE_RDMA_CM").is_some() {
+        gen_cm_bindings(&include_dirs);
+    }
+}
+
+/// Generate `librdmacm` bindings for the `rdma_cm` feature.
+///
+/// The bindings live in their own module and carry their own copies of the
+/// `ibv_*` types they depend on. Only pointers cross the boundary.
+fn gen_cm_bindings(include_dirs: &[String]) {
+    println!("cargo:rerun-if-changed=src/bindings/rdma_cma.h");
+    println!("cargo:rustc-link-lib=rdmacm");
+
+    let include_args = include_dirs.iter().map(|p| format!("-I{}", p));
+    let bindings = bindgen::builder()
+        .clang_args(include_args)
+        .header("src/bindings/rdma_cma.h")
+        .allowlist_function("rdma_.*")
+        .allowlist_type("rdma_.*")
+        .constified_enum_module("rdma_cm_event_type")
+        .constified_enum_module("rdma_port_space")
+        .derive_copy(true)
+        .derive_debug(true)
+        .derive_default(true)
+        .layout_tests(false)
+        .prepend_enum_name(false)
+        .size_t_is_usize(true)
+        .generate()
+        .expect("failed to generate rdma_cm bindings");
+
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    let dest = Path::new(&out_dir).join("rdma_cm_bindings.rs");
+    bindings
+        .write_to_file(dest)
+        .expect("failed to write rdma_cm bindings");
 }
@@ -0,0 +1,57 @@
+use quanta::Instant;
+use rrddmma::prelude::*;
+
+const NUM_QPS: usize = 256;
+
+fn make_qps(pd: &Pd, cq: &Cq, port: &Port) -> anyhow::Result<Vec<Qp>> {
+    (0..NUM_QPS)
+        .map(|_| {
+            let mut qp = Qp::builder()
+                .qp_type(QpType::Rc)
+                .caps(QpCaps::default())
+                .send_cq(cq)
+                .recv_cq(cq)
+                .sq_sig_all(false)
+                .build(pd)?;
+            qp.bind_local_port(port, None)?;
+            Ok(qp)
+        })
+        .collect()
+}
+
+fn endpoints(qps: &[Qp]) -> Vec<QpEndpoint> {
+    qps.iter().map(|qp| qp.endpoint().unwrap()).collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    let Nic { context, ports } = Nic::finder().dev_name("mlx5_0").probe()?;
+    let pd = Pd::new(&context)?;
+    let cq = Cq::new(&context, Cq::DEFAULT_CQ_DEPTH)?;
+
+    // Connect pairs of local QPs one by one.
+    let (mut left, mut right) = (
+        make_qps(&pd, &cq, &ports[0])?,
+        make_qps(&pd, &cq, &ports[0])?,
+    );
+    let (left_eps, right_eps) = (endpoints(&left), endpoints(&right));
+    let time = Instant::now();
+    for (qp, ep) in left.iter_mut().zip(&right_eps) {
+        qp.bind_peer(*ep, None)?;
+    }
+    for (qp, ep) in right.iter_mut().zip(&left_eps) {
+        qp.bind_peer(*ep, None)?;
+    }
+    println!("{} QPs one by one: {:?}", 2 * NUM_QPS, time.elapsed());
+
+    // Connect the same number of fresh QPs in batches.
+    let (mut left, mut right) = (
+        make_qps(&pd, &cq, &ports[0])?,
+        make_qps(&pd, &cq, &ports[0])?,
+    );
+    let (left_eps, right_eps) = (endpoints(&left), endpoints(&right));
+    let time = Instant::now();
+    Qp::connect_batch(&mut left, &right_eps)?;
+    Qp::connect_batch(&mut right, &left_eps)?;
+    println!("{} QPs in batches: {:?}", 2 * NUM_QPS, time.elapsed());
+    Ok(())
+}
@@ -19,7 +19,7 @@ fn make_qp(dev: &str) -> anyhow::Result<Qp> {
 fn main() -> anyhow::Result<()> {
     let mut qp = make_qp("mlx5_0")?;
     let ep = qp.endpoint().unwrap();
-    qp.bind_peer(ep)?;
+    qp.bind_peer(ep, None)?;
 
     // Receive a message from the client.
     let mut mem0 = RegisteredMem::new(qp.pd(), 4096)?;
@@ -0,0 +1,39 @@
+use rrddmma::{
+    prelude::*,
+    wrap::{latency, RegisteredMem},
+};
+
+const SAMPLES: usize = 100_000;
+
+fn main() -> anyhow::Result<()> {
+    let Nic { context, ports } = Nic::finder().dev_name("mlx5_0").probe()?;
+    let pd = Pd::new(&context)?;
+    let cq = Cq::new(&context, Cq::DEFAULT_CQ_DEPTH)?;
+    let mut qp = Qp::builder()
+        .qp_type(QpType::Rc)
+        .caps(QpCaps::default())
+        .send_cq(&cq)
+        .recv_cq(&cq)
+        .sq_sig_all(false)
+        .build(&pd)?;
+    qp.bind_local_port(&ports[0], None)?;
+    qp.bind_peer(qp.endpoint().unwrap(), None)?;
+
+    let src = RegisteredMem::new(&pd, 64)?;
+    let dst = RegisteredMem::new(&pd, 64)?;
+    let remote = dst.mr().as_remote();
+
+    // A single SGE, whose SGL is built on the stack.
+    let single = [src.as_slice()];
+    let stats = latency::measure_write(&qp, &single, &remote, SAMPLES)?;
+    println!("64B write, 1 SGE:  {:?}", stats);
+
+    // The same 64 bytes in more SGEs than fit on the stack, whose SGL is
+    // allocated on the heap on every post.
+    let split = (0..8)
+        .map(|i| src.slice(i * 8, 8).unwrap())
+        .collect::<Vec<_>>();
+    let stats = latency::measure_write(&qp, &split, &remote, SAMPLES)?;
+    println!("64B write, 8 SGEs: {:?}", stats);
+    Ok(())
+}
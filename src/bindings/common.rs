@@ -308,6 +308,25 @@ pub unsafe fn ibv_poll_cq(
     (*(*cq).context).ops.poll_cq.unwrap()(cq, num_entries, wc)
 }
 
+/// Request completion notification on a CQ.
+///
+/// An event will be added to the completion channel associated with the CQ
+/// when an entry is added to the CQ.
+///
+/// # Arguments
+///
+/// - `cq`: the completion queue to request notification for.
+/// - `solicited_only`: if non-zero, an event will be generated only for the
+///   next solicited CQ entry. If zero, any CQ entry, solicited or not, will
+///   generate an event.
+#[inline]
+pub unsafe fn ibv_req_notify_cq(
+    cq: *mut ibv_cq,
+    solicited_only: ::std::os::raw::c_int,
+) -> ::std::os::raw::c_int {
+    (*(*cq).context).ops.req_notify_cq.unwrap()(cq, solicited_only)
+}
+
 /// Post a list of work requests to a send queue.
 ///
 /// If IBV_SEND_INLINE flag is set, the data buffers can be reused
@@ -76,7 +76,14 @@ pub struct ibv_send_wr {
     pub imm_data_invalidated_rkey_union: imm_data_invalidated_rkey_union_t,
     pub wr: wr_t,
     pub qp_type_xrc_remote_srq_num: qp_type_xrc_remote_srq_num_union_t,
-    pub bind_mw: mw_rkey_bind_info_union_t,
+    pub bind_mw_tso: bind_mw_tso_union_t,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union bind_mw_tso_union_t {
+    pub bind_mw: bind_mw_t,
+    pub tso: tso_t,
 }
 
 impl ibv_send_wr {
@@ -174,3 +181,83 @@ pub unsafe fn ___ibv_query_port(
         )
     }
 }
+
+/// Create a completion queue with extended attributes.
+#[inline]
+pub unsafe fn ibv_create_cq_ex(
+    context: *mut ibv_context,
+    cq_attr: *mut ibv_cq_init_attr_ex,
+) -> *mut ibv_cq_ex {
+    let vctx = verbs_get_ctx_op!(context, create_cq_ex);
+    if vctx.is_null() {
+        *__errno_location() = EOPNOTSUPP;
+        std::ptr::null_mut()
+    } else {
+        (*vctx).create_cq_ex.unwrap()(context, cq_attr)
+    }
+}
+
+/// Start a batch of work requests on a QP created with send operation flags.
+#[inline]
+pub unsafe fn ibv_wr_start(qp: *mut ibv_qp_ex) {
+    (*qp).wr_start.unwrap()(qp)
+}
+
+/// Post the batch of work requests started by [`ibv_wr_start`].
+#[inline]
+pub unsafe fn ibv_wr_complete(qp: *mut ibv_qp_ex) -> c_int {
+    (*qp).wr_complete.unwrap()(qp)
+}
+
+/// Discard the batch of work requests started by [`ibv_wr_start`].
+#[inline]
+pub unsafe fn ibv_wr_abort(qp: *mut ibv_qp_ex) {
+    (*qp).wr_abort.unwrap()(qp)
+}
+
+/// Append an RDMA write to the current batch.
+#[inline]
+pub unsafe fn ibv_wr_rdma_write(qp: *mut ibv_qp_ex, rkey: u32, remote_addr: u64) {
+    (*qp).wr_rdma_write.unwrap()(qp, rkey, remote_addr)
+}
+
+/// Append an RDMA write with immediate to the current batch.
+#[inline]
+pub unsafe fn ibv_wr_rdma_write_imm(
+    qp: *mut ibv_qp_ex,
+    rkey: u32,
+    remote_addr: u64,
+    imm_data: __be32,
+) {
+    (*qp).wr_rdma_write_imm.unwrap()(qp, rkey, remote_addr, imm_data)
+}
+
+/// Append an RDMA read to the current batch.
+#[inline]
+pub unsafe fn ibv_wr_rdma_read(qp: *mut ibv_qp_ex, rkey: u32, remote_addr: u64) {
+    (*qp).wr_rdma_read.unwrap()(qp, rkey, remote_addr)
+}
+
+/// Append an RDMA send to the current batch.
+#[inline]
+pub unsafe fn ibv_wr_send(qp: *mut ibv_qp_ex) {
+    (*qp).wr_send.unwrap()(qp)
+}
+
+/// Append an RDMA send with immediate to the current batch.
+#[inline]
+pub unsafe fn ibv_wr_send_imm(qp: *mut ibv_qp_ex, imm_data: __be32) {
+    (*qp).wr_send_imm.unwrap()(qp, imm_data)
+}
+
+/// Set the only SGE of the last appended work request.
+#[inline]
+pub unsafe fn ibv_wr_set_sge(qp: *mut ibv_qp_ex, lkey: u32, addr: u64, length: u32) {
+    (*qp).wr_set_sge.unwrap()(qp, lkey, addr, length)
+}
+
+/// Set the SGL of the last appended work request.
+#[inline]
+pub unsafe fn ibv_wr_set_sge_list(qp: *mut ibv_qp_ex, num_sge: usize, sg_list: *const ibv_sge) {
+    (*qp).wr_set_sge_list.unwrap()(qp, num_sge, sg_list)
+}
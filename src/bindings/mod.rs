@@ -29,6 +29,12 @@ mod private {
 
 pub(crate) use self::private::*;
 
+/// Bindings of librdmacm C interfaces.
+#[cfg(feature = "rdma_cm")]
+pub(crate) mod cm {
+    include!(concat!(env!("OUT_DIR"), "/rdma_cm_bindings.rs"));
+}
+
 #[cfg(not(manual_mlx5))]
 mod __ibv_get_device_list_mod {
     use super::ibv_device;
@@ -160,6 +166,15 @@ pub use self::private::ibv_mr;
 /// Memory region permissions.
 pub use self::private::ibv_access_flags;
 
+/// Allocate a memory window.
+pub use self::private::ibv_alloc_mw;
+
+/// Deallocate a memory window.
+pub use self::private::ibv_dealloc_mw;
+
+/// Memory window.
+pub use self::private::ibv_mw;
+
 /// Create a queue pair.
 pub use self::private::ibv_create_qp;
 
@@ -0,0 +1 @@
+#include <rdma/rdma_cma.h>
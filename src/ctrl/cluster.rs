@@ -0,0 +1,641 @@
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind, Read, Write};
+use std::net::*;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::{fs, thread};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::connecter::{connect_until_success, stream_read, stream_write};
+use crate::rdma::cq::Cq;
+use crate::rdma::nic::PortState;
+use crate::rdma::pd::Pd;
+use crate::rdma::qp::{Qp, QpEndpoint, QpType};
+use crate::rdma::type_alias::{GidIndex, PortNum};
+
+/// RDMA device selection of a node, as given in the cluster configuration.
+/// Unspecified fields are left to the user's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceSpec {
+    /// Name of the RDMA device, e.g., `mlx5_0`.
+    #[serde(default)]
+    pub device: Option<String>,
+
+    /// Port number on the device.
+    #[serde(default)]
+    pub port: Option<PortNum>,
+
+    /// GID index on the port.
+    #[serde(default)]
+    pub gid_index: Option<GidIndex>,
+}
+
+impl DeviceSpec {
+    /// Return `true` if no field is specified.
+    fn is_empty(&self) -> bool {
+        self.device.is_none() && self.port.is_none() && self.gid_index.is_none()
+    }
+}
+
+/// A peer entry in the cluster configuration file: either a bare IP address
+/// or a table with the IP address and device overrides.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PeerConfig {
+    Ip(Ipv4Addr),
+    Detailed {
+        ip: Ipv4Addr,
+        #[serde(flatten)]
+        spec: DeviceSpec,
+    },
+}
+
+/// Cluster configuration file.
+#[derive(Deserialize)]
+struct ClusterConfig {
+    peers: Vec<PeerConfig>,
+}
+
+/// A group of nodes connected with each other through TCP, each identified
+/// by its rank.
+///
+/// On creation, every node establishes a TCP connection with every other
+/// node. The connections then serve as the control plane of collective
+/// operations. Like MPI collectives, every node must invoke the same sequence
+/// of collective operations with consistent arguments.
+pub struct Cluster {
+    /// Rank of this node.
+    rank: usize,
+
+    /// Addresses of all nodes, indexed by rank.
+    nodes: Vec<SocketAddrV4>,
+
+    /// Connections to all nodes, indexed by rank. `None` for this node.
+    streams: Vec<Option<TcpStream>>,
+
+    /// Device overrides of all nodes, indexed by rank.
+    devices: Vec<Option<DeviceSpec>>,
+
+    /// Number of barriers entered by this node.
+    barrier_generation: AtomicU64,
+}
+
+impl Cluster {
+    /// The default TCP port to use.
+    pub const DEFAULT_PORT: u16 = 13338;
+
+    /// Create a cluster of the given nodes, where this node has the given
+    /// rank, and connect to all other nodes. Every node listens on
+    /// [`Cluster::DEFAULT_PORT`].
+    ///
+    /// This method blocks until all nodes have joined the cluster.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `rank` is out of range.
+    pub fn new(rank: usize, nodes: &[Ipv4Addr]) -> io::Result<Self> {
+        let nodes = nodes
+            .iter()
+            .map(|ip| SocketAddrV4::new(*ip, Self::DEFAULT_PORT))
+            .collect();
+        Self::new_with_addrs(rank, nodes)
+    }
+
+    /// Create a cluster of nodes listening on the given addresses, where this
+    /// node has the given rank, and connect to all other nodes.
+    ///
+    /// This method blocks until all nodes have joined the cluster.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `rank` is out of range.
+    pub fn new_with_addrs(rank: usize, nodes: Vec<SocketAddrV4>) -> io::Result<Self> {
+        assert!(
+            rank < nodes.len(),
+            "rank {} out of range for {} nodes",
+            rank,
+            nodes.len()
+        );
+
+        let listener = TcpListener::bind(SocketAddrV4::new(
+            Ipv4Addr::new(0, 0, 0, 0),
+            nodes[rank].port(),
+        ))?;
+        let mut streams = (0..nodes.len()).map(|_| None).collect::<Vec<_>>();
+
+        // Connect to lower ranks, and then accept connections from higher ranks.
+        for (peer, addr) in nodes.iter().enumerate().take(rank) {
+            let mut stream = connect_until_success(*addr, Duration::from_millis(200))?;
+            stream.write_all(&(rank as u32).to_le_bytes())?;
+            streams[peer] = Some(stream);
+        }
+        for _ in rank + 1..nodes.len() {
+            let mut stream = listener.accept()?.0;
+            let mut buf = [0; 4];
+            stream.read_exact(&mut buf)?;
+
+            let peer = u32::from_le_bytes(buf) as usize;
+            if peer <= rank || peer >= nodes.len() || streams[peer].is_some() {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidData,
+                    format!("unexpected connection from rank {}", peer),
+                ));
+            }
+            streams[peer] = Some(stream);
+        }
+
+        Ok(Self {
+            rank,
+            devices: vec![None; nodes.len()],
+            nodes,
+            streams,
+            barrier_generation: AtomicU64::new(0),
+        })
+    }
+
+    /// Create a cluster from a TOML configuration file, where this node has
+    /// the given rank, and connect to all other nodes. Every node listens on
+    /// [`Cluster::DEFAULT_PORT`].
+    ///
+    /// The file lists the nodes in rank order. Each entry is either a bare IP
+    /// address, or a table with the IP address and optional `device`, `port`
+    /// and `gid_index` overrides, which are returned by
+    /// [`Cluster::device_for()`]:
+    ///
+    /// ```toml
+    /// peers = [
+    ///     "10.0.0.1",
+    ///     { ip = "10.0.0.2", device = "mlx5_1", port = 1, gid_index = 3 },
+    /// ]
+    /// ```
+    ///
+    /// This method blocks until all nodes have joined the cluster.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `rank` is out of range.
+    pub fn load_toml(path: impl AsRef<Path>, rank: usize) -> io::Result<Self> {
+        let config = fs::read_to_string(path)?;
+        let config = toml::from_str::<ClusterConfig>(&config)
+            .map_err(|e| IoError::new(IoErrorKind::InvalidData, e))?;
+
+        let (ips, devices): (Vec<_>, Vec<_>) = config
+            .peers
+            .into_iter()
+            .map(|peer| match peer {
+                PeerConfig::Ip(ip) => (ip, None),
+                PeerConfig::Detailed { ip, spec } => (ip, Some(spec).filter(|s| !s.is_empty())),
+            })
+            .unzip();
+
+        let mut this = Self::new(rank, &ips)?;
+        this.devices = devices;
+        Ok(this)
+    }
+
+    /// Get the rank of this node.
+    #[inline]
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// Get the number of nodes in the cluster.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Get the addresses of all nodes, indexed by rank.
+    #[inline]
+    pub fn nodes(&self) -> &[SocketAddrV4] {
+        &self.nodes
+    }
+
+    /// Get the device overrides of the node of the given rank, if the cluster
+    /// was loaded from a configuration file that specifies any.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `rank` is out of range.
+    pub fn device_for(&self, rank: usize) -> Option<DeviceSpec> {
+        self.devices[rank].clone()
+    }
+
+    /// Get the connection to the node of the given rank.
+    fn stream(&self, rank: usize) -> &TcpStream {
+        self.streams[rank].as_ref().unwrap()
+    }
+
+    /// Block until all nodes have entered this barrier.
+    ///
+    /// The barrier uses the dissemination algorithm in `ceil(log2(n))` rounds
+    /// for `n` nodes: in round `k`, every node notifies the node `2^k` ranks
+    /// after it and waits for the notification of the node `2^k` ranks
+    /// before it.
+    ///
+    /// Every notification is tagged with the generation of the barrier, i.e.,
+    /// the number of barriers this node has entered, and the round. Fail with
+    /// `InvalidData` if a received tag does not match, which means that the
+    /// nodes disagree on the sequence of collective operations, e.g., a node
+    /// skipped a barrier, instead of letting a notification of one barrier
+    /// complete another and the job hang later.
+    pub fn barrier(&self) -> io::Result<()> {
+        let n = self.size();
+        let generation = self.barrier_generation.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let mut round = 0u64;
+        let mut dist = 1;
+        while dist < n {
+            let to = (self.rank + dist) % n;
+            let from = (self.rank + n - dist) % n;
+
+            let mut tag = [0u8; 16];
+            tag[..8].copy_from_slice(&generation.to_le_bytes());
+            tag[8..].copy_from_slice(&round.to_le_bytes());
+            stream_write(&mut self.stream(to), &tag)?;
+
+            let theirs = stream_read(&mut self.stream(from))?;
+            if theirs != tag {
+                let field = |i: usize| {
+                    theirs
+                        .get(i * 8..i * 8 + 8)
+                        .map_or(0, |b| u64::from_le_bytes(b.try_into().unwrap()))
+                };
+                return Err(IoError::new(
+                    IoErrorKind::InvalidData,
+                    format!(
+                        "barrier mismatch with rank {}: ours generation {} round {}, \
+                         theirs generation {} round {}",
+                        from,
+                        generation,
+                        round,
+                        field(0),
+                        field(1)
+                    ),
+                ));
+            }
+            round += 1;
+            dist *= 2;
+        }
+        Ok(())
+    }
+
+    /// Broadcast a value from the root node to all other nodes.
+    ///
+    /// The root must provide the value, which is returned on all nodes.
+    /// Other nodes should pass `None`; their values are ignored anyway.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if `root` is out of range.
+    /// - Panic if this node is the root but does not provide a value.
+    pub fn broadcast<T: Serialize + DeserializeOwned>(
+        &self,
+        root: usize,
+        value: Option<T>,
+    ) -> io::Result<T> {
+        assert!(root < self.size(), "root {} out of range", root);
+
+        if self.rank == root {
+            let value = value.expect("root must provide the value to broadcast");
+            let buf = serde_json::to_vec(&value)?;
+            for peer in (0..self.size()).filter(|&peer| peer != root) {
+                stream_write(&mut self.stream(peer), &buf)?;
+            }
+            Ok(value)
+        } else {
+            let buf = stream_read(&mut self.stream(root))?;
+            Ok(serde_json::from_slice(&buf)?)
+        }
+    }
+
+    /// Send a message to one node and receive one from another at the same
+    /// time, as every node sends first in the rounds of collectives.
+    fn shift(&self, to: usize, from: usize, buf: &[u8]) -> io::Result<Vec<u8>> {
+        thread::scope(|s| {
+            let sender = s.spawn(|| stream_write(&mut self.stream(to), buf));
+            let theirs = stream_read(&mut self.stream(from));
+            sender.join().expect("sender thread panicked")?;
+            theirs
+        })
+    }
+
+    /// Gather a value from every node to all nodes. Return the values indexed
+    /// by rank, including the local one.
+    ///
+    /// This is useful for exchanging per-node metadata, e.g., `QpEndpoint`s
+    /// and `MrRemote`s, in all-to-all topologies.
+    ///
+    /// The values are gathered with Bruck's algorithm in `ceil(log2(n))`
+    /// rounds for `n` nodes. In each round, every node sends all values it
+    /// has gathered so far to one node as a single message, and receives the
+    /// same from another. Compared to exchanging with every other node in
+    /// turn, the number of sequential round trips drops from `n - 1` to
+    /// `ceil(log2(n))`, e.g., from 999 to 10 for 1000 nodes, while the total
+    /// amount of data received by each node stays the same. With the
+    /// `compression` feature, the messages are compressed.
+    pub fn all_gather<T: Serialize + DeserializeOwned>(&self, local: T) -> io::Result<Vec<T>> {
+        let n = self.size();
+
+        // `values[i]` is the value of rank `(rank + i) mod n`.
+        let mut values = vec![serde_json::to_value(&local)?];
+        let mut dist = 1;
+        while dist < n {
+            let to = (self.rank + n - dist) % n;
+            let from = (self.rank + dist) % n;
+            let theirs = self.shift(to, from, &encode(&values)?)?;
+
+            values.extend(decode::<Vec<serde_json::Value>>(&theirs)?);
+            values.truncate(n);
+            dist *= 2;
+        }
+
+        values.rotate_right(self.rank);
+        values
+            .into_iter()
+            .map(|value| Ok(serde_json::from_value(value)?))
+            .collect()
+    }
+
+    /// Send a distinct value to every node, and receive the value that every
+    /// node sends to this node. `values` is indexed by the destination rank,
+    /// and the returned values by the source rank; the entry of this node is
+    /// passed through.
+    ///
+    /// The values are exchanged with Bruck's algorithm in `ceil(log2(n))`
+    /// rounds for `n` nodes. In round `k`, every node forwards the values
+    /// whose distance to their destination has bit `k` set to the node `2^k`
+    /// ranks ahead, as a single message. Each node thus sends about
+    /// `n * log2(n) / 2` values in total instead of `n - 1`, in exchange for
+    /// `log2(n)` instead of `n - 1` sequential round trips, which is the
+    /// better trade-off for the small values of connection setup. With the
+    /// `compression` feature, the messages are compressed.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `values` does not have one entry per node.
+    pub fn all_to_all<T: Serialize + DeserializeOwned>(
+        &self,
+        values: Vec<T>,
+    ) -> io::Result<Vec<T>> {
+        let n = self.size();
+        assert_eq!(values.len(), n, "values must be given for every node");
+
+        // `blocks[i]` is destined to rank `(rank + i) mod n`.
+        let mut blocks = values
+            .into_iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        blocks.rotate_left(self.rank);
+
+        let mut dist = 1;
+        while dist < n {
+            let to = (self.rank + dist) % n;
+            let from = (self.rank + n - dist) % n;
+            let outgoing = (0..n).filter(|i| i & dist != 0).collect::<Vec<_>>();
+
+            let ours = outgoing
+                .iter()
+                .map(|&i| blocks[i].take())
+                .collect::<Vec<_>>();
+            let theirs = self.shift(to, from, &encode(&ours)?)?;
+            let theirs = decode::<Vec<serde_json::Value>>(&theirs)?;
+            if theirs.len() != outgoing.len() {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidData,
+                    format!(
+                        "rank {} sent {} values, expected {}",
+                        from,
+                        theirs.len(),
+                        outgoing.len()
+                    ),
+                ));
+            }
+            for (i, value) in outgoing.into_iter().zip(theirs) {
+                blocks[i] = value;
+            }
+            dist *= 2;
+        }
+
+        // `blocks[i]` now comes from rank `(rank - i) mod n`.
+        blocks.reverse();
+        blocks.rotate_right(self.rank + 1);
+        blocks
+            .into_iter()
+            .map(|value| Ok(serde_json::from_value(value)?))
+            .collect()
+    }
+
+    /// Connect QPs with all other nodes to build a fully-connected mesh.
+    ///
+    /// `qps` is indexed by rank, and holds the QPs to connect to each node,
+    /// which must be already bound to local ports. The entry of this node is
+    /// ignored. Every pair of nodes must have the same number of QPs for each
+    /// other.
+    ///
+    /// The endpoints of all QPs are exchanged at once with
+    /// [`Cluster::all_to_all()`], in `ceil(log2(n))` rounds of one message
+    /// each for `n` nodes, and the QPs for each peer are then connected
+    /// locally with [`Qp::connect_batch()`]. Exchanging with every peer in
+    /// turn would take `n - 1` sequential round trips instead. As the
+    /// exchange dominates the setup of large meshes, which is bound by
+    /// round-trip latency rather than bandwidth, the setup time is expected
+    /// to shrink roughly by a factor of `(n - 1) / ceil(log2(n))`, e.g.,
+    /// about 100x for 1000 nodes.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if `qps` does not have one entry per node.
+    /// - Panic if any QP is not RC or not bound to a local port.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(rank = self.rank, size = self.size()), err)
+    )]
+    pub fn connect_fc(&self, qps: &mut [Vec<Qp>]) -> io::Result<()> {
+        assert_eq!(qps.len(), self.size(), "QPs must be given for every node");
+
+        let table = qps
+            .iter()
+            .enumerate()
+            .map(|(peer, qps)| {
+                if peer == self.rank {
+                    return Vec::new();
+                }
+                qps.iter()
+                    .map(|qp| qp.endpoint().expect("QP not bound to a local port"))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let theirs = self.all_to_all(table)?;
+
+        for (peer, theirs) in theirs.into_iter().enumerate() {
+            if peer == self.rank {
+                continue;
+            }
+            if theirs.len() != qps[peer].len() {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidData,
+                    format!(
+                        "rank {} has {} QPs for us, but we have {}",
+                        peer,
+                        theirs.len(),
+                        qps[peer].len()
+                    ),
+                ));
+            }
+            Qp::connect_batch(&mut qps[peer], &theirs)?;
+        }
+        Ok(())
+    }
+
+    /// Build a fully-connected mesh of `num_links` QPs per pair of nodes,
+    /// exchanging endpoints through a user-supplied transport instead of the
+    /// TCP control plane of a `Cluster`, e.g., a discovery service that the
+    /// deployment already runs. This node has rank `rank` among `size` nodes.
+    /// Return the connected QPs indexed by rank, with an empty entry for this
+    /// node.
+    ///
+    /// Every QP is created on `pd` with the default capabilities and a CQ of
+    /// its own, shared by its send and receive queues, and bound to the first
+    /// active port of the device with the recommended GID.
+    ///
+    /// `exchange(peer, local)` must publish the local endpoint `local` to the
+    /// node `peer`, and return the endpoint that `peer` published for this
+    /// node. It is called for each peer in ascending rank order, and
+    /// `num_links` times in a row for each peer, once per link; the `k`-th
+    /// call for a peer must be matched with that peer's `k`-th call for this
+    /// node. As every node publishes before waiting, the calls cannot
+    /// deadlock as long as publishing does not block on the peer.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if `rank` is out of range.
+    /// - Panic if `qp_type` is neither RC nor UC.
+    pub fn connect_fc_with_exchange(
+        rank: usize,
+        size: usize,
+        pd: &Pd,
+        num_links: usize,
+        qp_type: QpType,
+        mut exchange: impl FnMut(usize, QpEndpoint) -> io::Result<QpEndpoint>,
+    ) -> io::Result<Vec<Vec<Qp>>> {
+        assert!(rank < size, "rank {} out of range {}", rank, size);
+        assert!(
+            matches!(qp_type, QpType::Rc | QpType::Uc),
+            "QP type must be RC or UC"
+        );
+
+        let ctx = pd.context();
+        let port = ctx
+            .ports()
+            .iter()
+            .filter_map(|&num| ctx.query_port(num).ok())
+            .find(|port| port.state() == PortState::Active)
+            .ok_or_else(|| IoError::new(IoErrorKind::NotConnected, "no active port"))?;
+
+        let mut qps = Vec::with_capacity(size);
+        for peer in 0..size {
+            if peer == rank {
+                qps.push(Vec::new());
+                continue;
+            }
+
+            let mut links = Vec::with_capacity(num_links);
+            let mut theirs = Vec::with_capacity(num_links);
+            for _ in 0..num_links {
+                let cq = Cq::new(ctx, Cq::DEFAULT_CQ_DEPTH).map_err(IoError::other)?;
+                let mut qp = Qp::builder()
+                    .qp_type(qp_type)
+                    .send_cq(&cq)
+                    .recv_cq(&cq)
+                    .sq_sig_all(false)
+                    .build(pd)
+                    .map_err(IoError::other)?;
+                qp.bind_local_port(&port, None)?;
+
+                theirs.push(exchange(peer, qp.endpoint().unwrap())?);
+                links.push(qp);
+            }
+
+            if qp_type == QpType::Rc {
+                Qp::connect_batch(&mut links, &theirs)?;
+            } else {
+                for (qp, ep) in links.iter_mut().zip(theirs) {
+                    qp.bind_peer(ep, None)?;
+                }
+            }
+            qps.push(links);
+        }
+        Ok(qps)
+    }
+}
+
+/// Serialize a message of a collective operation, compressing it with the
+/// `compression` feature. All nodes must agree on the feature.
+fn encode<T: Serialize>(value: &T) -> io::Result<Vec<u8>> {
+    let buf = serde_json::to_vec(value)?;
+    #[cfg(feature = "compression")]
+    let buf = lz4_flex::compress_prepend_size(&buf);
+    Ok(buf)
+}
+
+/// Deserialize a message of a collective operation serialized by [`encode`].
+fn decode<T: DeserializeOwned>(buf: &[u8]) -> io::Result<T> {
+    #[cfg(feature = "compression")]
+    let buf = &lz4_flex::decompress_size_prepended(buf)
+        .map_err(|e| IoError::new(IoErrorKind::InvalidData, e))?;
+    Ok(serde_json::from_slice(buf)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `f` on every node of a cluster of `n` nodes on localhost, and
+    /// return the results indexed by rank.
+    fn run_cluster<R: Send>(n: usize, f: impl Fn(&Cluster) -> io::Result<R> + Sync) -> Vec<R> {
+        let nodes = (0..n)
+            .map(|_| {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+                SocketAddrV4::new(Ipv4Addr::LOCALHOST, listener.local_addr().unwrap().port())
+            })
+            .collect::<Vec<_>>();
+
+        thread::scope(|s| {
+            let handles = (0..n)
+                .map(|rank| {
+                    let (nodes, f) = (nodes.clone(), &f);
+                    s.spawn(move || f(&Cluster::new_with_addrs(rank, nodes)?))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap().unwrap())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn all_gather_collects_every_rank() {
+        for n in [1, 2, 3, 5, 8] {
+            let gathered = run_cluster(n, |cluster| cluster.all_gather(cluster.rank() * 10));
+            for values in gathered {
+                assert_eq!(values, (0..n).map(|rank| rank * 10).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn all_to_all_delivers_every_pair() {
+        for n in [1, 2, 3, 5, 8] {
+            let received = run_cluster(n, |cluster| {
+                let values = (0..n).map(|dst| (cluster.rank(), dst)).collect();
+                cluster.all_to_all(values)
+            });
+            for (rank, values) in received.into_iter().enumerate() {
+                assert_eq!(values, (0..n).map(|src| (src, rank)).collect::<Vec<_>>());
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::mem;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::ptr::{self, NonNull};
+
+use crate::bindings::cm::*;
+use crate::bindings::{ibv_qp_attr, ibv_qp_state};
+use crate::rdma::context::Context;
+use crate::rdma::cq::Cq;
+use crate::rdma::pd::Pd;
+use crate::rdma::qp::*;
+
+/// Timeout of address and route resolution, in milliseconds.
+const RESOLVE_TIMEOUT_MS: i32 = 2000;
+
+/// Build a `sockaddr_in` from an IPv4 socket address.
+fn sockaddr_of(addr: SocketAddrV4) -> libc::sockaddr_in {
+    libc::sockaddr_in {
+        sin_family: libc::AF_INET as _,
+        sin_port: addr.port().to_be(),
+        sin_addr: libc::in_addr {
+            s_addr: u32::from(*addr.ip()).to_be(),
+        },
+        sin_zero: [0; 8],
+    }
+}
+
+/// Connection builder with `rdma_cm`.
+///
+/// Unlike [`Connecter`](super::Connecter), which exchanges QP endpoints over a
+/// TCP side channel, `CmConnecter` lets `librdmacm` resolve the peer's IP
+/// address to a device, port, GID and path, and performs the connection
+/// handshake over the RDMA fabric. This is the usual way to connect to peers
+/// that are not `rrddmma` programs.
+///
+/// The connecter creates the QP on the device that `rdma_cm` picks, together
+/// with its own protection domain and a CQ shared by sends and receives. Use
+/// [`Qp::pd()`] to register memory regions for the connection.
+///
+/// The connection is torn down when the connecter is dropped.
+pub struct CmConnecter {
+    /// The connected QP. Must be dropped before the CM ID.
+    qp: Option<Qp>,
+
+    /// The CM ID of the connection (or of the listener, before a connection
+    /// request arrives).
+    id: *mut rdma_cm_id,
+
+    /// The event channel of the CM ID.
+    channel: *mut rdma_event_channel,
+
+    /// Whether the connection has been established.
+    established: bool,
+}
+
+impl CmConnecter {
+    /// The default TCP port space port to use.
+    pub const DEFAULT_PORT: u16 = 13339;
+
+    /// Create a connecter that establishes an RC connection on the given port.
+    ///
+    /// - If `with` is `Some`, connect to the specified IP address. The server
+    ///   must be already listening, or the connection is rejected.
+    /// - If `with` is `None`, listen on the given port and accept the first
+    ///   incoming connection request.
+    ///
+    /// The created RC QP has the given capabilities and does not signal all
+    /// sends.
+    pub fn new_on_port(with: Option<Ipv4Addr>, port: u16, caps: QpCaps) -> io::Result<Self> {
+        // SAFETY: FFI.
+        let channel = unsafe { rdma_create_event_channel() };
+        if channel.is_null() {
+            return Err(IoError::last_os_error());
+        }
+
+        let mut this = Self {
+            qp: None,
+            id: ptr::null_mut(),
+            channel,
+            established: false,
+        };
+        // SAFETY: FFI.
+        let ret = unsafe {
+            rdma_create_id(
+                channel,
+                &mut this.id,
+                ptr::null_mut(),
+                rdma_port_space::RDMA_PS_TCP,
+            )
+        };
+        if ret != 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        match with {
+            Some(ip) => this.connect(SocketAddrV4::new(ip, port), caps)?,
+            None => this.accept(port, caps)?,
+        }
+        Ok(this)
+    }
+
+    /// Create a connecter that establishes an RC connection on
+    /// [`CmConnecter::DEFAULT_PORT`]. See [`CmConnecter::new_on_port()`] for
+    /// more information.
+    pub fn new(with: Option<Ipv4Addr>, caps: QpCaps) -> io::Result<Self> {
+        Self::new_on_port(with, Self::DEFAULT_PORT, caps)
+    }
+
+    /// Get the connected QP.
+    #[inline]
+    pub fn qp(&self) -> &Qp {
+        self.qp.as_ref().unwrap()
+    }
+
+    /// Get the connected QP as mutable.
+    #[inline]
+    pub fn qp_mut(&mut self) -> &mut Qp {
+        self.qp.as_mut().unwrap()
+    }
+
+    /// Client side: resolve the server and connect to it.
+    fn connect(&mut self, addr: SocketAddrV4, caps: QpCaps) -> io::Result<()> {
+        let mut dst = sockaddr_of(addr);
+        // SAFETY: FFI.
+        let ret = unsafe {
+            rdma_resolve_addr(
+                self.id,
+                ptr::null_mut(),
+                (&mut dst as *mut libc::sockaddr_in).cast(),
+                RESOLVE_TIMEOUT_MS,
+            )
+        };
+        self.check(ret)?;
+        self.wait_event(rdma_cm_event_type::RDMA_CM_EVENT_ADDR_RESOLVED)?;
+
+        // SAFETY: FFI.
+        let ret = unsafe { rdma_resolve_route(self.id, RESOLVE_TIMEOUT_MS) };
+        self.check(ret)?;
+        self.wait_event(rdma_cm_event_type::RDMA_CM_EVENT_ROUTE_RESOLVED)?;
+
+        self.create_qp(caps)?;
+        self.transition(ibv_qp_state::IBV_QPS_INIT)?;
+
+        let mut param = self.conn_param();
+        // SAFETY: FFI.
+        let ret = unsafe { rdma_connect(self.id, &mut param) };
+        self.check(ret)?;
+        self.wait_event(rdma_cm_event_type::RDMA_CM_EVENT_CONNECT_RESPONSE)?;
+
+        // The QP is not created by `rdma_cm`, so it must be brought up and the
+        // connection must be established manually.
+        self.transition(ibv_qp_state::IBV_QPS_RTR)?;
+        self.transition(ibv_qp_state::IBV_QPS_RTS)?;
+
+        // SAFETY: FFI.
+        let ret = unsafe { rdma_establish(self.id) };
+        self.check(ret)?;
+        self.established = true;
+        Ok(())
+    }
+
+    /// Server side: listen on the port and accept the first connection.
+    fn accept(&mut self, port: u16, caps: QpCaps) -> io::Result<()> {
+        let mut addr = sockaddr_of(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port));
+        // SAFETY: FFI.
+        let ret = unsafe { rdma_bind_addr(self.id, (&mut addr as *mut libc::sockaddr_in).cast()) };
+        self.check(ret)?;
+
+        // SAFETY: FFI.
+        let ret = unsafe { rdma_listen(self.id, 1) };
+        self.check(ret)?;
+
+        // Replace the listener ID with the connection ID, as the listener is
+        // of no use after the connection request arrives.
+        let conn_id = self.wait_event(rdma_cm_event_type::RDMA_CM_EVENT_CONNECT_REQUEST)?;
+        // SAFETY: FFI.
+        unsafe { rdma_destroy_id(self.id) };
+        self.id = conn_id;
+
+        self.create_qp(caps)?;
+        self.transition(ibv_qp_state::IBV_QPS_INIT)?;
+        self.transition(ibv_qp_state::IBV_QPS_RTR)?;
+        self.transition(ibv_qp_state::IBV_QPS_RTS)?;
+
+        let mut param = self.conn_param();
+        // SAFETY: FFI.
+        let ret = unsafe { rdma_accept(self.id, &mut param) };
+        self.check(ret)?;
+        self.wait_event(rdma_cm_event_type::RDMA_CM_EVENT_ESTABLISHED)?;
+        self.established = true;
+        Ok(())
+    }
+
+    /// Create the RC QP on the device that the CM ID is bound to.
+    fn create_qp(&mut self, caps: QpCaps) -> io::Result<()> {
+        // SAFETY: the CM ID is valid and bound to a device after address
+        // resolution or connection request.
+        let verbs = unsafe { (*self.id).verbs };
+        let verbs = NonNull::new(verbs.cast())
+            .ok_or_else(|| IoError::new(IoErrorKind::NotFound, "CM ID is not bound to a device"))?;
+
+        // SAFETY: `rdma_cm` keeps its device contexts open until the library
+        // is unloaded.
+        let ctx = unsafe { Context::from_raw(verbs, false) }?;
+        let pd = Pd::new(&ctx)?;
+        let cq = Cq::new(&ctx, Cq::DEFAULT_CQ_DEPTH).map_err(IoError::other)?;
+        let qp = Qp::builder()
+            .qp_type(QpType::Rc)
+            .caps(caps)
+            .send_cq(&cq)
+            .recv_cq(&cq)
+            .sq_sig_all(false)
+            .build(&pd)
+            .map_err(IoError::other)?;
+
+        self.qp = Some(qp);
+        Ok(())
+    }
+
+    /// Modify the QP to the given state with attributes provided by `rdma_cm`.
+    fn transition(&self, state: ibv_qp_state::Type) -> io::Result<()> {
+        // SAFETY: POD type.
+        let mut attr = unsafe { mem::zeroed::<ibv_qp_attr>() };
+        let mut attr_mask = 0;
+        attr.qp_state = state;
+
+        // SAFETY: FFI, and `ibv_qp_attr` is the same C type in both bindings.
+        let ret = unsafe {
+            rdma_init_qp_attr(
+                self.id,
+                (&mut attr as *mut ibv_qp_attr).cast(),
+                &mut attr_mask,
+            )
+        };
+        self.check(ret)?;
+        self.qp().modify_raw(&mut attr, attr_mask)
+    }
+
+    /// Connection parameters to send with the connection request or reply.
+    fn conn_param(&self) -> rdma_conn_param {
+        rdma_conn_param {
+            responder_resources: 16,
+            initiator_depth: 16,
+            retry_count: 6,
+            rnr_retry_count: 6,
+            qp_num: self.qp().qp_num(),
+            ..Default::default()
+        }
+    }
+
+    /// Wait for the next event on the channel, and return the CM ID that the
+    /// event is reported on. Fail if it is not of the expected type.
+    fn wait_event(&self, expected: rdma_cm_event_type::Type) -> io::Result<*mut rdma_cm_id> {
+        let mut event = ptr::null_mut();
+        // SAFETY: FFI.
+        let ret = unsafe { rdma_get_cm_event(self.channel, &mut event) };
+        self.check(ret)?;
+
+        // SAFETY: `rdma_get_cm_event` returning 0 means `event` is valid.
+        let (ty, id, status) = unsafe { ((*event).event, (*event).id, (*event).status) };
+
+        // SAFETY: FFI, and every event must be acknowledged exactly once.
+        unsafe { rdma_ack_cm_event(event) };
+
+        match ty {
+            _ if ty == expected => Ok(id),
+            rdma_cm_event_type::RDMA_CM_EVENT_REJECTED => Err(IoError::new(
+                IoErrorKind::ConnectionRefused,
+                format!("connection rejected (status {})", status),
+            )),
+            _ => Err(IoError::other(format!(
+                "unexpected rdma_cm event {} (expected {}, status {})",
+                ty, expected, status
+            ))),
+        }
+    }
+
+    /// Convert the return value of an `rdma_*` call into a result.
+    /// These calls return -1 and set `errno` on failure.
+    fn check(&self, ret: i32) -> io::Result<()> {
+        match ret {
+            0 => Ok(()),
+            _ => Err(IoError::last_os_error()),
+        }
+    }
+}
+
+impl Drop for CmConnecter {
+    fn drop(&mut self) {
+        // SAFETY: FFI, and the CM ID and the channel are not used anymore.
+        unsafe {
+            if self.established {
+                rdma_disconnect(self.id);
+            }
+            self.qp.take();
+            if !self.id.is_null() {
+                rdma_destroy_id(self.id);
+            }
+            rdma_destroy_event_channel(self.channel);
+        }
+    }
+}
@@ -1,11 +1,16 @@
-use std::io::{self, Read, Write};
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind, Read, Write};
 use std::mem;
 use std::net::*;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+#[cfg(mlnx4)]
+use crate::rdma::dct::Dct;
 use crate::rdma::{mr::*, qp::*};
 
-fn stream_write(stream: &mut &TcpStream, buf: &[u8]) -> io::Result<()> {
+pub(super) fn stream_write(stream: &mut &TcpStream, buf: &[u8]) -> io::Result<()> {
     stream.write_all(&buf.len().to_le_bytes())?;
 
     let mut written = 0;
@@ -16,7 +21,7 @@ fn stream_write(stream: &mut &TcpStream, buf: &[u8]) -> io::Result<()> {
     Ok(())
 }
 
-fn stream_read(stream: &mut &TcpStream) -> io::Result<Vec<u8>> {
+pub(super) fn stream_read(stream: &mut &TcpStream) -> io::Result<Vec<u8>> {
     let mut buf = [0; mem::size_of::<usize>()];
     stream.read_exact(&mut buf)?;
     let len = usize::from_le_bytes(buf);
@@ -26,7 +31,7 @@ fn stream_read(stream: &mut &TcpStream) -> io::Result<Vec<u8>> {
     Ok(buf)
 }
 
-fn connect_until_success(
+pub(super) fn connect_until_success(
     server_addr: SocketAddrV4,
     wait_on_failure: Duration,
 ) -> io::Result<TcpStream> {
@@ -39,6 +44,31 @@ fn connect_until_success(
     }
 }
 
+/// Connection establishment error type.
+#[derive(Debug, Error)]
+pub enum ConnectError {
+    /// The TCP connection or (de)serialization returned an error.
+    #[error("I/O error from TCP connection")]
+    IoError(#[from] io::Error),
+
+    /// The remote peer uses a different wire-format version.
+    #[error("wire-format version mismatch: ours {ours}, theirs {theirs}")]
+    VersionMismatch { ours: u8, theirs: u8 },
+}
+
+/// Timing breakdown of [`Connecter::connect_many()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectTiming {
+    /// Number of QPs connected.
+    pub qps: usize,
+
+    /// Time spent exchanging endpoints with the remote peer.
+    pub exchange: Duration,
+
+    /// Time spent transitioning the QPs to RTS.
+    pub transition: Duration,
+}
+
 /// Connection manager that connects with a specific remote peer.
 pub struct Connecter {
     /// Remote peer information. If `Some`, this is the client side; otherwise,
@@ -63,19 +93,25 @@ impl Connecter {
         let ep_first = first.endpoint().unwrap();
         let ep_second = second.endpoint().unwrap();
         first
-            .bind_peer(ep_second)
-            .and_then(|_| second.bind_peer(ep_first))
+            .bind_peer(ep_second, None)
+            .and_then(|_| second.bind_peer(ep_first, None))
     }
 
     /// The default TCP port to use.
     pub const DEFAULT_PORT: u16 = 13337;
 
+    /// The wire-format version of the messages exchanged by `Connecter`s.
+    /// Two sides must agree on this version to connect.
+    pub const WIRE_VERSION: u8 = 1;
+
     /// Create a new `Connecter` that connects with the specified remote peer
     /// on the given TCP port.
     ///
     /// If the specified remote peer is `None`, this will be the server side.
     /// Otherwise, this will be the client side and will connect to the remote.
-    pub fn new_on_port(with: Option<Ipv4Addr>, port: u16) -> io::Result<Self> {
+    ///
+    /// Return an error if the remote peer uses a different wire-format version.
+    pub fn new_on_port(with: Option<Ipv4Addr>, port: u16) -> Result<Self, ConnectError> {
         let stream = if let Some(addr) = with.as_ref() {
             let server_addr = SocketAddrV4::new(*addr, port);
             connect_until_success(server_addr, Duration::from_millis(200))?
@@ -85,6 +121,18 @@ impl Connecter {
             listener.accept()?.0
         };
 
+        // Exchange wire-format versions.
+        let ours = Self::WIRE_VERSION;
+        let mut theirs = [0u8];
+        (&stream).write_all(&[ours])?;
+        (&stream).read_exact(&mut theirs)?;
+        if theirs[0] != ours {
+            return Err(ConnectError::VersionMismatch {
+                ours,
+                theirs: theirs[0],
+            });
+        }
+
         Ok(Self {
             with,
             stream: Some(stream),
@@ -92,7 +140,7 @@ impl Connecter {
     }
 
     /// Create a new `Connecter` that connects with the specified remote peer.
-    pub fn new(with: Option<Ipv4Addr>) -> io::Result<Self> {
+    pub fn new(with: Option<Ipv4Addr>) -> Result<Self, ConnectError> {
         Self::new_on_port(with, Self::DEFAULT_PORT)
     }
 
@@ -106,6 +154,10 @@ impl Connecter {
     /// # Panics
     ///
     /// Panic if the QP is not bound to a local port.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(qpn = qp.qp_num()), err)
+    )]
     pub fn connect(&self, qp: &mut Qp) -> io::Result<Option<QpPeer>> {
         let ep = qp.endpoint();
         let ep = serde_json::to_string(&ep)?;
@@ -129,7 +181,7 @@ impl Connecter {
         };
 
         if qp.qp_type() == QpType::Rc {
-            qp.bind_peer(ep)?;
+            qp.bind_peer(ep, None)?;
             Ok(None)
         } else {
             let sgid_index = if qp.use_global_routing() {
@@ -141,23 +193,148 @@ impl Connecter {
         }
     }
 
+    /// Connect many RC QPs with the remote peer, which must call this method
+    /// with the same number of QPs. The QPs must be already bound to local
+    /// ports. Return the time spent in each phase.
+    ///
+    /// Instead of exchanging endpoints and transitioning the QPs one by one
+    /// as [`Connecter::connect()`] does, this method exchanges the endpoints
+    /// of all QPs in a single round trip, and then brings them up with
+    /// [`Qp::connect_batch()`], which transitions them in parallel. The setup
+    /// time thus no longer grows with one TCP round trip and two firmware
+    /// commands per QP.
+    ///
+    /// # Panics
+    ///
+    /// Panic if any QP is not RC, is not bound to a local port, or is already
+    /// bound to a remote peer.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(qps = qps.len()), err)
+    )]
+    pub fn connect_many(&self, qps: &mut [Qp]) -> io::Result<ConnectTiming> {
+        let start = Instant::now();
+        let ours = Self::endpoints_of(qps);
+        let theirs = self.exchange_value(&ours)?;
+        Self::check_endpoint_count(&ours, &theirs)?;
+        let exchange = start.elapsed();
+
+        let start = Instant::now();
+        Qp::connect_batch(qps, &theirs)?;
+        Ok(ConnectTiming {
+            qps: qps.len(),
+            exchange,
+            transition: start.elapsed(),
+        })
+    }
+
+    /// Connect many RC QPs with the remote peer like
+    /// [`Connecter::connect_many()`], and exchange application metadata,
+    /// e.g., protocol versions or buffer layouts, in the same round trip.
+    /// Return the peers of the QPs, in order, and the metadata of the remote
+    /// peer, which must call this method with the same number of QPs and the
+    /// same metadata type.
+    ///
+    /// # Panics
+    ///
+    /// Panic if any QP is not RC, is not bound to a local port, or is already
+    /// bound to a remote peer.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(qps = qps.len()), err)
+    )]
+    pub fn connect_many_with<M: Serialize + DeserializeOwned>(
+        &self,
+        qps: &mut [Qp],
+        local_meta: M,
+    ) -> io::Result<(Vec<QpPeer>, M)> {
+        let ours = (Self::endpoints_of(qps), local_meta);
+        let (theirs, meta) = self.exchange_value(&ours)?;
+        Self::check_endpoint_count(&ours.0, &theirs)?;
+        Qp::connect_batch(qps, &theirs)?;
+
+        let peers = qps.iter().map(|qp| qp.peer().unwrap().clone()).collect();
+        Ok((peers, meta))
+    }
+
+    /// Get the endpoints of the given QPs.
+    fn endpoints_of(qps: &[Qp]) -> Vec<QpEndpoint> {
+        qps.iter()
+            .map(|qp| qp.endpoint().expect("QP not bound to a local port"))
+            .collect()
+    }
+
+    /// Check that the remote peer has as many QPs for us as we have for it.
+    fn check_endpoint_count(ours: &[QpEndpoint], theirs: &[QpEndpoint]) -> io::Result<()> {
+        if theirs.len() != ours.len() {
+            return Err(IoError::new(
+                IoErrorKind::InvalidData,
+                format!(
+                    "remote peer has {} QPs for us, but we have {}",
+                    theirs.len(),
+                    ours.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Send a value to the remote side and receive its counterpart. The
+    /// client receives first, so that the two sides never both wait.
+    fn exchange_value<T: Serialize + DeserializeOwned>(&self, ours: &T) -> io::Result<T> {
+        if self.with.is_some() {
+            let theirs = self.recv_value::<T>()?;
+            self.send_value(ours)?;
+            Ok(theirs)
+        } else {
+            self.send_value(ours)?;
+            self.recv_value::<T>()
+        }
+    }
+
+    /// Serialize a value and send it to the remote side.
+    pub(crate) fn send_value<T: Serialize>(&self, value: &T) -> io::Result<()> {
+        let buf = serde_json::to_string(value)?;
+        let mut stream = self.stream.as_ref().unwrap();
+        stream_write(&mut stream, buf.as_bytes())
+    }
+
+    /// Receive a value sent by [`Self::send_value()`] from the remote side.
+    pub(crate) fn recv_value<T: DeserializeOwned>(&self) -> io::Result<T> {
+        let mut stream = self.stream.as_ref().unwrap();
+        let buf = stream_read(&mut stream)?;
+        let value = serde_json::from_slice::<T>(buf.as_slice())?;
+        Ok(value)
+    }
+
     /// Send a local MR's information to the remote side.
     ///
     /// This method accepts a `MrSlice` instead of a `Mr` to let the sender
     /// control what part of the MR to send.
     pub fn send_mr(&self, slice: MrRemote) -> io::Result<()> {
-        let mr = serde_json::to_string(&slice)?;
-        let mut stream = self.stream.as_ref().unwrap();
-        stream_write(&mut stream, mr.as_bytes())?;
-
-        Ok(())
+        self.send_value(&slice)
     }
 
     /// Receive sent MR information from the opponent's side.
     pub fn recv_mr(&self) -> io::Result<MrRemote> {
-        let mut stream = self.stream.as_ref().unwrap();
-        let buf = stream_read(&mut stream)?;
-        let mr = serde_json::from_slice::<MrRemote>(buf.as_slice())?;
-        Ok(mr)
+        self.recv_value()
+    }
+
+    /// Send a local DCT's endpoint information to the remote side.
+    #[cfg(mlnx4)]
+    pub fn send_dct(&self, dct: &Dct) -> io::Result<()> {
+        self.send_value(&dct.endpoint())
+    }
+
+    /// Receive sent DCT information from the opponent's side, and make a
+    /// peer out of it that is reachable from the given DC initiator QP.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the QP is not bound to a local port.
+    #[cfg(mlnx4)]
+    pub fn recv_dct_peer(&self, qp: &Qp) -> io::Result<QpPeer> {
+        let ep = self.recv_value::<QpEndpoint>()?;
+        qp.make_peer(ep)
     }
 }
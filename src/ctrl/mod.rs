@@ -1,6 +1,17 @@
 //! Connection management utilities.
 
+/// TCP-based cluster of nodes.
+mod cluster;
+
 /// TCP-based connection builder.
 mod connecter;
 
-pub use connecter::Connecter;
+/// `rdma_cm`-based connection builder.
+#[cfg(feature = "rdma_cm")]
+mod cm_connecter;
+
+pub use cluster::{Cluster, DeviceSpec};
+pub use connecter::{ConnectError, ConnectTiming, Connecter};
+
+#[cfg(feature = "rdma_cm")]
+pub use cm_connecter::CmConnecter;
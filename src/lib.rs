@@ -28,6 +28,7 @@ pub mod rdma;
 
 /// Error types generated by RDMA functionalities.
 pub mod errors {
+    pub use super::ctrl::ConnectError;
     pub use super::rdma::cq::CqCreationError;
     pub use super::rdma::gid::GidQueryError;
     pub use super::rdma::nic::{NicProbeError, PortQueryError};
@@ -40,3 +41,6 @@ pub mod errors {
 pub mod ctrl;
 pub mod mlnx;
 pub mod wrap;
+
+pub use mlnx::{backend_info, BackendInfo, BackendVariant};
+pub use rdma::fork::{fork_init, set_auto_fork_init};
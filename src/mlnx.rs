@@ -1,6 +1,9 @@
 //! MLNX_OFED version-based feature flag.
 
+use crate::rdma::qp::ExpFeature;
+
 /// Possibble MLNX_OFED versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MlnxVersion {
     /// MLNX_OFED v5.x or newer.
     /// RDMA-Core features are available.
@@ -20,3 +23,44 @@ pub const MLNX_VERSION: MlnxVersion = MlnxVersion::Mlnx5;
 /// Note that this value is not always the same as the document -- it depends on your deployment environment.
 #[cfg(mlnx4)]
 pub const MLNX_VERSION: MlnxVersion = MlnxVersion::Mlnx4;
+
+/// The driver path of the verbs backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendVariant {
+    /// The legacy experimental verbs of MLNX_OFED v4.x.
+    Legacy,
+
+    /// RDMA-Core verbs of MLNX_OFED v5.x or newer, or upstream `libibverbs`.
+    RdmaCore,
+}
+
+/// The verbs backend that rrddmma is built against.
+/// Returned by [`backend_info()`](crate::backend_info).
+#[derive(Debug, Clone)]
+pub struct BackendInfo {
+    /// The driver path.
+    pub variant: BackendVariant,
+
+    /// Experimental features that the backend can use. Whether a device
+    /// actually supports them is reported by
+    /// [`Context::supports()`](crate::rdma::context::Context::supports).
+    pub exp_features: Vec<ExpFeature>,
+}
+
+/// Get information about the verbs backend that rrddmma is built against,
+/// e.g., to be included in bug reports.
+pub fn backend_info() -> BackendInfo {
+    #[cfg(mlnx4)]
+    let (variant, exp_features) = (
+        BackendVariant::Legacy,
+        vec![ExpFeature::ExtendedAtomics, ExpFeature::DynamicConnected],
+    );
+
+    #[cfg(mlnx5)]
+    let (variant, exp_features) = (BackendVariant::RdmaCore, Vec::new());
+
+    BackendInfo {
+        variant,
+        exp_features,
+    }
+}
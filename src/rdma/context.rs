@@ -1,11 +1,15 @@
 //! Device context.
 
-use std::io;
+use std::io::{self, Error as IoError};
+use std::mem::MaybeUninit;
 use std::os::fd::AsRawFd;
 use std::ptr::NonNull;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use super::nic::*;
+use super::qp::ExpFeature;
+use super::type_alias::PortNum;
 use crate::bindings::*;
 use crate::utils::interop::{from_c_err, from_c_ret};
 
@@ -58,18 +62,62 @@ struct ContextInner {
     ctx: IbvContext,
     attr: ibv_device_attr,
 
+    /// Whether to close the device on drop.
+    owned: bool,
+
+    /// Name of the device, e.g., `mlx5_0`.
+    dev_name: String,
+
+    /// Numbers of the ports that this context was opened for.
+    ports: Vec<PortNum>,
+
+    /// Number of QPs created on this context and not yet destroyed.
+    live_qps: AtomicU32,
+
+    /// Serializes resource creation on this context.
+    create_lock: Mutex<()>,
+
     #[cfg(mlnx4)]
     clock_info: ibv_exp_clock_info,
 }
 
 impl Drop for ContextInner {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
+
         // SAFETY: call only once, and no UAF since I will be dropped.
         unsafe { self.ctx.close() }.expect("cannot close context on drop");
     }
 }
 
 /// Device context.
+///
+/// The device is closed when the last `Context` handle is dropped. Every
+/// resource created from a context, directly or indirectly (i.e., [`Pd`],
+/// [`Cq`], [`Qp`], [`Mr`], [`Srq`] and [`QpPeer`]), holds a strong reference
+/// to it. Therefore, it is safe to drop your own `Context` handle while such
+/// resources are still alive; the device will not be closed out from under
+/// them.
+///
+/// [`Pd`]: crate::prelude::Pd
+/// [`Cq`]: crate::prelude::Cq
+/// [`Qp`]: crate::prelude::Qp
+/// [`Mr`]: crate::prelude::Mr
+/// [`Srq`]: crate::prelude::Srq
+/// [`QpPeer`]: crate::prelude::QpPeer
+///
+/// # Thread safety
+///
+/// Resources can be created from the same context, or from resources of the
+/// same context, in multiple threads concurrently, e.g., to set up many
+/// connections from a thread pool. Creation calls are serialized by a lock in
+/// the context, which is off the data path.
+///
+/// Posting work requests and polling completions are not covered by the lock.
+/// Posting to the same QP from multiple threads requires external
+/// synchronization, e.g., [`SharedQp`](crate::rdma::qp::SharedQp).
 #[derive(Clone)]
 pub struct Context {
     /// Cached context pointer.
@@ -84,7 +132,15 @@ impl Context {
     #[cfg(not(mlnx4))]
     pub(crate) fn new(ctx: IbvContext, attr: ibv_device_attr) -> Self {
         Self {
-            inner: Arc::new(ContextInner { ctx, attr }),
+            inner: Arc::new(ContextInner {
+                ctx,
+                attr,
+                owned: true,
+                dev_name: ctx.dev().name().unwrap_or_default(),
+                ports: (1..=attr.phys_port_cnt).collect(),
+                live_qps: AtomicU32::new(0),
+                create_lock: Mutex::new(()),
+            }),
             ctx,
         }
     }
@@ -102,6 +158,11 @@ impl Context {
             inner: Arc::new(ContextInner {
                 ctx,
                 attr,
+                owned: true,
+                dev_name: ctx.dev().name().unwrap_or_default(),
+                ports: (1..=attr.phys_port_cnt).collect(),
+                live_qps: AtomicU32::new(0),
+                create_lock: Mutex::new(()),
                 clock_info,
             }),
             ctx,
@@ -110,6 +171,56 @@ impl Context {
 }
 
 impl Context {
+    /// Open the device with the given node GUID, in host byte order as shown
+    /// by `ibv_devinfo`, e.g., `0x248a_0703_00a1_b2c3` for
+    /// `248a:0703:00a1:b2c3`.
+    ///
+    /// Unlike device names, which can be reordered across reboots, node GUIDs
+    /// are stable, and thus suit configuration files. The context covers all
+    /// physical ports of the device.
+    ///
+    /// Return [`NicProbeError::NotFound`] if no device has the GUID.
+    pub fn open_by_guid(node_guid: u64) -> Result<Self, NicProbeError> {
+        let dev_list = IbvDeviceList::new()?;
+        let dev = dev_list
+            .iter()
+            .find(|dev| dev.guid() == node_guid)
+            .ok_or(NicProbeError::NotFound)?;
+
+        let ctx = dev.open()?;
+        match ctx.query_device() {
+            Ok(attr) => Ok(Self::new(ctx, attr)),
+            Err(e) => {
+                // SAFETY: call only once and no UAF.
+                unsafe { ctx.close()? };
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Wrap an already-opened device context, e.g., one obtained from C
+    /// libraries or `rdma_cm`.
+    ///
+    /// If `owned` is `true`, the device is closed when the last `Context`
+    /// handle is dropped, like contexts opened by this crate. Otherwise, the
+    /// caller remains responsible for closing it.
+    ///
+    /// # Safety
+    ///
+    /// - `ctx` must point to a valid, opened device context.
+    /// - If `owned` is `true`, the context must not be closed elsewhere.
+    /// - If `owned` is `false`, the context must outlive this `Context` and
+    ///   every resource created from it.
+    pub unsafe fn from_raw(ctx: NonNull<ibv_context>, owned: bool) -> io::Result<Self> {
+        let ctx = IbvContext::from(ctx);
+        let attr = ctx.query_device()?;
+        let mut this = Self::new(ctx, attr);
+
+        // The context was just created, so this is the only reference.
+        Arc::get_mut(&mut this.inner).unwrap().owned = owned;
+        Ok(this)
+    }
+
     /// Get the underlying [`ibv_context`] pointer.
     pub fn as_raw(&self) -> *mut ibv_context {
         self.ctx.as_ptr()
@@ -137,11 +248,159 @@ impl Context {
         Ok(ctx.as_ptr())
     }
 
+    /// Restrict the ports of the context to those matched when probing.
+    /// Must be called right after creation, when this is the only handle.
+    pub(crate) fn set_ports(&mut self, ports: Vec<PortNum>) {
+        Arc::get_mut(&mut self.inner).unwrap().ports = ports;
+    }
+
+    /// Get the name of the device, e.g., `mlx5_0`, which is useful to
+    /// identify the NIC that a resource uses in logs.
+    ///
+    /// The name is empty if it could not be retrieved when opening the device.
+    #[inline]
+    pub fn device_name(&self) -> &str {
+        &self.inner.dev_name
+    }
+
+    /// Get the numbers of the ports that this context was opened for.
+    ///
+    /// For contexts probed with [`NicFinder::probe_nth_port()`], this is the
+    /// matched port only; otherwise, it is all physical ports of the device.
+    /// Use [`Context::query_port()`] to get the port information.
+    #[inline]
+    pub fn ports(&self) -> &[PortNum] {
+        &self.inner.ports
+    }
+
+    /// Get the node GUID of the device, in host byte order.
+    /// See [`Context::open_by_guid()`] for details.
+    #[inline]
+    pub fn node_guid(&self) -> u64 {
+        u64::from_be(self.inner.attr.node_guid)
+    }
+
+    /// Get the NUMA node that the device is installed on, as reported by
+    /// sysfs. Fail if it is unknown, e.g., on systems without NUMA support.
+    ///
+    /// Allocate buffers on this node with
+    /// [`wrap::numa::alloc_on()`](crate::wrap::numa::alloc_on) to keep the
+    /// NIC's memory accesses local.
+    pub fn numa_node(&self) -> io::Result<u8> {
+        self.ctx.dev().numa_node()
+    }
+
     /// Get the device attributes.
     pub fn attr(&self) -> &ibv_device_attr {
         &self.inner.attr
     }
 
+    /// Return `true` if the device supports the given experimental feature.
+    ///
+    /// Always return `false` unless built against MLNX_OFED v4.x, which is the
+    /// only backend with experimental features. See [`crate::backend_info()`].
+    pub fn supports(&self, feature: ExpFeature) -> bool {
+        #[cfg(mlnx4)]
+        {
+            // SAFETY: POD type.
+            let mut attr = unsafe { std::mem::zeroed::<ibv_exp_device_attr>() };
+            attr.comp_mask =
+                ibv_exp_device_attr_comp_mask::IBV_EXP_DEVICE_ATTR_EXP_CAP_FLAGS.0 as _;
+            // SAFETY: FFI.
+            let ret = unsafe { ibv_exp_query_device(self.as_raw(), &mut attr) };
+            if ret != 0 {
+                return false;
+            }
+
+            let flag = match feature {
+                ExpFeature::ExtendedAtomics => ibv_exp_device_cap_flags::IBV_EXP_DEVICE_EXT_ATOMICS,
+                ExpFeature::DynamicConnected => {
+                    ibv_exp_device_cap_flags::IBV_EXP_DEVICE_DC_TRANSPORT
+                }
+            };
+            attr.exp_device_cap_flags & flag.0 as u64 != 0
+        }
+
+        #[cfg(mlnx5)]
+        {
+            let _ = feature;
+            false
+        }
+    }
+
+    /// Get the maximum number of QPs supported by the device.
+    #[inline]
+    pub fn max_qp(&self) -> u32 {
+        self.inner.attr.max_qp as _
+    }
+
+    /// Get the maximum number of CQs supported by the device.
+    #[inline]
+    pub fn max_cq(&self) -> u32 {
+        self.inner.attr.max_cq as _
+    }
+
+    /// Get the maximum number of MRs supported by the device.
+    #[inline]
+    pub fn max_mr(&self) -> u32 {
+        self.inner.attr.max_mr as _
+    }
+
+    /// Get the maximum number of PDs supported by the device.
+    #[inline]
+    pub fn max_pd(&self) -> u32 {
+        self.inner.attr.max_pd as _
+    }
+
+    /// Get the number of completion vectors of the device, i.e., the valid
+    /// range of the completion vector that CQs report events to.
+    ///
+    /// Completion vectors are typically backed by distinct interrupt lines,
+    /// so spreading notifiable CQs across them distributes the interrupt
+    /// load across CPUs.
+    #[inline]
+    pub fn num_comp_vectors(&self) -> u32 {
+        // SAFETY: the pointed-to `ibv_context` instance is valid.
+        (unsafe { (*self.as_raw()).num_comp_vectors }) as u32
+    }
+
+    /// Get the number of QPs created on this context and not yet destroyed.
+    #[inline]
+    pub fn live_qps(&self) -> u32 {
+        self.inner.live_qps.load(Ordering::Relaxed)
+    }
+
+    /// Return `true` if `n` more QPs fit in the device limit, taking into
+    /// account the QPs already created on this context.
+    ///
+    /// **NOTE:** the device limit is shared by all processes and contexts on
+    /// the device, whose QPs are not counted. Hence, a `true` result does not
+    /// guarantee that the QP creations will succeed.
+    pub fn can_create_qps(&self, n: u32) -> bool {
+        self.live_qps()
+            .checked_add(n)
+            .is_some_and(|total| total <= self.max_qp())
+    }
+
+    /// Lock resource creation on this context, until the guard is dropped.
+    pub(crate) fn lock_creation(&self) -> MutexGuard<'_, ()> {
+        // Resource creation does not leave the lock's data inconsistent.
+        self.inner
+            .create_lock
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record the creation of a QP on this context.
+    pub(crate) fn qp_created(&self) {
+        self.inner.live_qps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the destruction of a QP on this context.
+    pub(crate) fn qp_destroyed(&self) {
+        self.inner.live_qps.fetch_sub(1, Ordering::Relaxed);
+    }
+
     /// Get the clock information.
     #[cfg(mlnx4)]
     pub fn clock_info(&self) -> &ibv_exp_clock_info {
@@ -149,6 +408,136 @@ impl Context {
     }
 }
 
+impl Context {
+    /// Query the current information of the port with the given index.
+    ///
+    /// Port information is a snapshot taken at query time. Use this method to
+    /// refresh it, e.g., after receiving a [`PortEvent`].
+    pub fn query_port(&self, num: u8) -> Result<Port, PortQueryError> {
+        Port::new(self.ctx, num)
+    }
+
+    /// Get an iterator over port state changes of the device.
+    ///
+    /// The iterator blocks until the device reports the next port state change,
+    /// unless the file descriptor returned by [`Context::as_raw_fd()`] is set
+    /// to non-blocking mode, in which case it yields a `WouldBlock` error when
+    /// there are no pending events.
+    ///
+    /// **NOTE:** the iterator consumes all asynchronous events of the device,
+    /// acknowledging and discarding those unrelated to port state. Use
+    /// [`Context::async_events()`] to receive other events as well.
+    pub fn port_events(&self) -> PortEvents<'_> {
+        PortEvents {
+            events: self.async_events(),
+        }
+    }
+
+    /// Get an iterator over the asynchronous events of the device that this
+    /// crate recognizes, i.e., port state changes, SRQ limit events, and
+    /// fatal QP errors. The iterator blocks in the same way as
+    /// [`Context::port_events()`].
+    ///
+    /// **NOTE:** the iterator consumes all asynchronous events of the device,
+    /// acknowledging and discarding unrecognized ones.
+    pub fn async_events(&self) -> AsyncEvents<'_> {
+        AsyncEvents { ctx: self }
+    }
+}
+
+/// Asynchronous event of a device, as reported by [`Context::async_events()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncEvent {
+    /// A port state change.
+    Port(PortEvent),
+
+    /// The number of receive requests posted to the SRQ with the contained
+    /// number dropped below the limit set by
+    /// [`Srq::set_limit()`](crate::rdma::srq::Srq::set_limit). The limit is
+    /// disarmed, and must be set again to get another event.
+    SrqLimitReached(u32),
+
+    /// The QP with the contained number hit a fatal error, a request error,
+    /// or a local access error, and transitioned to the error state. The QP
+    /// must be reset and reconnected before it can be used again.
+    QpFatal(u32),
+}
+
+/// Iterator over port state changes of a device.
+/// Created by [`Context::port_events()`].
+pub struct PortEvents<'a> {
+    events: AsyncEvents<'a>,
+}
+
+impl Iterator for PortEvents<'_> {
+    type Item = io::Result<PortEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.events.next()? {
+                Ok(AsyncEvent::Port(event)) => return Some(Ok(event)),
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Iterator over the recognized asynchronous events of a device.
+/// Created by [`Context::async_events()`].
+pub struct AsyncEvents<'a> {
+    ctx: &'a Context,
+}
+
+impl Iterator for AsyncEvents<'_> {
+    type Item = io::Result<AsyncEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut event = <MaybeUninit<ibv_async_event>>::uninit();
+            // SAFETY: FFI.
+            let ret = unsafe { ibv_get_async_event(self.ctx.as_raw(), event.as_mut_ptr()) };
+            if ret != 0 {
+                return Some(Err(IoError::last_os_error()));
+            }
+
+            // SAFETY: `ibv_get_async_event` returning 0 means `event` is initialized.
+            let mut event = unsafe { event.assume_init() };
+            let async_event = match event.event_type {
+                // SAFETY: port events carry the port number in the union.
+                ibv_event_type::IBV_EVENT_PORT_ACTIVE => Some(AsyncEvent::Port(PortEvent::Active(
+                    unsafe { event.element.port_num } as _,
+                ))),
+                ibv_event_type::IBV_EVENT_PORT_ERR => Some(AsyncEvent::Port(PortEvent::Down(
+                    unsafe { event.element.port_num } as _,
+                ))),
+                ibv_event_type::IBV_EVENT_SRQ_LIMIT_REACHED => {
+                    // SAFETY: SRQ events carry the SRQ in the union, which
+                    // cannot be destroyed before the event is acknowledged.
+                    let mut num = 0;
+                    let ret = unsafe { ibv_get_srq_num(event.element.srq, &mut num) };
+                    (ret == 0).then_some(AsyncEvent::SrqLimitReached(num))
+                }
+                ibv_event_type::IBV_EVENT_QP_FATAL
+                | ibv_event_type::IBV_EVENT_QP_REQ_ERR
+                | ibv_event_type::IBV_EVENT_QP_ACCESS_ERR => {
+                    // SAFETY: QP events carry the QP in the union, which
+                    // cannot be destroyed before the event is acknowledged.
+                    let qpn = unsafe { (*event.element.qp).qp_num };
+                    Some(AsyncEvent::QpFatal(qpn))
+                }
+                _ => None,
+            };
+
+            // SAFETY: FFI, and every event must be acknowledged exactly once.
+            unsafe { ibv_ack_async_event(&mut event) };
+            if let Some(async_event) = async_event {
+                return Some(Ok(async_event));
+            }
+        }
+    }
+}
+
 impl AsRawFd for Context {
     /// Get the `cmd_fd` of the context.
     fn as_raw_fd(&self) -> std::os::unix::prelude::RawFd {
@@ -0,0 +1,94 @@
+use super::{Cq, CqCreationError};
+use crate::rdma::context::Context;
+
+/// Completion queue builder.
+#[derive(Debug, Clone)]
+pub struct CqBuilder {
+    /// Capacity of the CQ.
+    pub(super) capacity: u32,
+
+    /// Whether the CQ ignores overruns.
+    pub(super) ignore_overrun: bool,
+
+    /// Whether the CQ has its own completion channel.
+    pub(super) notifiable: bool,
+
+    /// Completion vector that the CQ reports events to.
+    pub(super) comp_vector: u32,
+}
+
+impl CqBuilder {
+    /// Create a new completion queue builder.
+    pub fn new() -> Self {
+        Self {
+            capacity: Cq::DEFAULT_CQ_DEPTH,
+            ignore_overrun: false,
+            notifiable: false,
+            comp_vector: 0,
+        }
+    }
+
+    /// Set the capacity of the CQ.
+    /// If not set, [`Cq::DEFAULT_CQ_DEPTH`] is used.
+    pub fn capacity(mut self, capacity: u32) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set whether the CQ ignores overruns. Default is `false`.
+    ///
+    /// By default, a CQ that overruns, i.e., receives a completion when it is
+    /// full, enters the error state, and so do all QPs using it. With this
+    /// option set, the CQ survives overruns at the cost of silently dropping
+    /// completions, which some throughput-oriented designs prefer over losing
+    /// all connections. Only use it if losing completions is acceptable.
+    ///
+    /// This option requires `ibv_create_cq_ex` and device support. If either
+    /// is missing, [`CqBuilder::build()`] fails with
+    /// [`CqCreationError::UnsupportedFeature`].
+    pub fn ignore_overrun(mut self, ignore_overrun: bool) -> Self {
+        self.ignore_overrun = ignore_overrun;
+        self
+    }
+
+    /// Set whether the CQ reports completion events to its own completion
+    /// channel, like CQs created by [`Cq::new_notifiable()`]. Default is
+    /// `false`.
+    ///
+    /// This option cannot be combined with
+    /// [`CqBuilder::ignore_overrun()`]; [`CqBuilder::build()`] fails with
+    /// [`CqCreationError::UnsupportedFeature`] if both are set.
+    pub fn notifiable(mut self, notifiable: bool) -> Self {
+        self.notifiable = notifiable;
+        self
+    }
+
+    /// Set the completion vector that the CQ reports events to, which must be
+    /// less than [`Context::num_comp_vectors()`]. Default is `0`.
+    ///
+    /// See [`CompVectorAllocator`](crate::wrap::CompVectorAllocator) for
+    /// spreading CQs across the vectors automatically.
+    pub fn comp_vector(mut self, comp_vector: u32) -> Self {
+        self.comp_vector = comp_vector;
+        self
+    }
+
+    /// Build the completion queue on the given context.
+    pub fn build(self, ctx: &Context) -> Result<Cq, CqCreationError> {
+        match (self.ignore_overrun, self.notifiable) {
+            (true, true) => Err(CqCreationError::UnsupportedFeature(
+                "ignore_overrun on notifiable CQs",
+            )),
+            (true, false) => Cq::new_ignore_overrun(ctx, self.capacity, self.comp_vector),
+            (false, notifiable) => {
+                Cq::new_on_vector(ctx, self.capacity, notifiable, self.comp_vector)
+            }
+        }
+    }
+}
+
+impl Default for CqBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
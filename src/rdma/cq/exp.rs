@@ -16,10 +16,21 @@ impl ExpWc {
     }
 
     /// Get the completion status.
+    /// Status codes unknown to this crate are reported as [`WcStatus::Unknown`].
     #[inline]
     pub fn status(&self) -> WcStatus {
-        // SAFETY: SAFETY: enum constraints of `libibverbs`.
-        unsafe { WcStatus::from_unsafe(self.0.status) }
+        WcStatus::from(self.0.status)
+    }
+
+    /// Get the completion status, without checking whether the status code
+    /// is known to this crate.
+    ///
+    /// # Safety
+    ///
+    /// See [`Wc::status_unchecked()`].
+    #[inline]
+    pub unsafe fn status_unchecked(&self) -> WcStatus {
+        WcStatus::from_unsafe(self.0.status)
     }
 
     /// Read the timestamp of the work completion.
@@ -1,23 +1,37 @@
 //! Completion queue and Work completion.
 
+mod builder;
 mod exp;
+#[cfg(feature = "tokio")]
+mod stream;
 mod wc;
 
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
+use std::hash::Hash;
 use std::io::{self, Error as IoError};
 use std::mem::MaybeUninit;
 use std::ptr::{self, NonNull};
-use std::sync::Arc;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
 use thiserror::Error;
 
+pub use self::builder::*;
 #[cfg(mlnx4)]
 pub use self::exp::*;
+#[cfg(feature = "tokio")]
+pub use self::stream::*;
 pub use self::wc::*;
 use super::context::Context;
 use crate::bindings::*;
 use crate::utils::interop::from_c_ret;
 
+/// Number of work completions that [`Cq::poll_round_robin()`] polls from the
+/// CQ at a time.
+const ROUND_ROBIN_BATCH: usize = 32;
+
 /// Wrapper for `*mut ibv_cq`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
@@ -43,12 +57,51 @@ impl_ibv_wrapper_traits!(ibv_cq, IbvCq);
 struct CqInner {
     ctx: Context,
     cq: IbvCq,
+
+    /// Work completions put back into the CQ, returned before new ones.
+    stash: Mutex<VecDeque<Wc>>,
+
+    /// Whether `stash` is non-empty, checked without locking on every poll.
+    stashed: AtomicBool,
+
+    /// Completion channel that the CQ reports events to, if any.
+    channel: Option<NonNull<ibv_comp_channel>>,
+
+    /// Whether the CQ was created to ignore overruns.
+    ignore_overrun: bool,
+
+    /// Completion vector that the CQ reports events to.
+    comp_vector: u32,
+}
+
+impl CqInner {
+    fn new(ctx: &Context, cq: IbvCq) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            cq,
+            stash: Mutex::new(VecDeque::new()),
+            stashed: AtomicBool::new(false),
+            channel: None,
+            ignore_overrun: false,
+            comp_vector: 0,
+        }
+    }
 }
 
+// SAFETY: the completion channel is only used through thread-safe verbs.
+unsafe impl Send for CqInner {}
+unsafe impl Sync for CqInner {}
+
 impl Drop for CqInner {
     fn drop(&mut self) {
         // SAFETY: call only once, and no UAF since I will be dropped.
         unsafe { self.cq.destroy() }.expect("cannot destroy CQ on drop");
+
+        if let Some(channel) = self.channel {
+            // SAFETY: FFI, and the CQ using the channel is already destroyed.
+            let ret = unsafe { ibv_destroy_comp_channel(channel.as_ptr()) };
+            from_c_ret(ret).expect("cannot destroy completion channel on drop");
+        }
     }
 }
 
@@ -74,10 +127,41 @@ impl Cq {
 
     /// Create a new completion queue.
     pub fn new(ctx: &Context, capacity: u32) -> Result<Cq, CqCreationError> {
+        Self::new_on_vector(ctx, capacity, false, 0)
+    }
+
+    /// Create a new completion queue that reports completion events to its
+    /// own completion channel, so that it can be waited on without polling,
+    /// e.g., with [`Cq::into_stream()`].
+    ///
+    /// Events are only generated after requesting notifications, which the
+    /// users of the channel do on their own.
+    pub fn new_notifiable(ctx: &Context, capacity: u32) -> Result<Cq, CqCreationError> {
+        Self::new_on_vector(ctx, capacity, true, 0)
+    }
+
+    /// Create a new completion queue on the given completion vector, with its
+    /// own completion channel if `notifiable` is set.
+    pub(crate) fn new_on_vector(
+        ctx: &Context,
+        capacity: u32,
+        notifiable: bool,
+        comp_vector: u32,
+    ) -> Result<Cq, CqCreationError> {
         let max_capacity = ctx.attr().max_cqe as u32;
         if capacity > max_capacity {
             return Err(CqCreationError::TooManyCqes(max_capacity));
         }
+        Self::check_comp_vector(ctx, comp_vector)?;
+
+        let _guard = ctx.lock_creation();
+        let channel = if notifiable {
+            // SAFETY: FFI.
+            let channel = unsafe { ibv_create_comp_channel(ctx.as_raw()) };
+            Some(NonNull::new(channel).ok_or_else(IoError::last_os_error)?)
+        } else {
+            None
+        };
 
         // SAFETY: FFI.
         let cq = unsafe {
@@ -85,22 +169,96 @@ impl Cq {
                 ctx.as_raw(),
                 capacity as i32,
                 ptr::null_mut(),
-                ptr::null_mut(),
-                0,
+                channel.map_or(ptr::null_mut(), NonNull::as_ptr),
+                comp_vector as i32,
             )
         };
-        let cq = NonNull::new(cq).ok_or_else(IoError::last_os_error)?;
+        let Some(cq) = NonNull::new(cq) else {
+            let err = IoError::last_os_error();
+            if let Some(channel) = channel {
+                // SAFETY: FFI, and the channel is not used by any CQ.
+                unsafe { ibv_destroy_comp_channel(channel.as_ptr()) };
+            }
+            return Err(err.into());
+        };
         let cq = IbvCq::from(cq);
 
+        let mut inner = CqInner::new(ctx, cq);
+        inner.channel = channel;
+        inner.comp_vector = comp_vector;
+        Ok(Self {
+            inner: Arc::new(inner),
+            cq,
+        })
+    }
+
+    /// Check that the completion vector exists on the device.
+    fn check_comp_vector(ctx: &Context, comp_vector: u32) -> Result<(), CqCreationError> {
+        // Some providers report no completion vectors, but accept vector 0.
+        let num = ctx.num_comp_vectors();
+        if comp_vector != 0 && comp_vector >= num {
+            return Err(CqCreationError::InvalidCompVector(comp_vector, num));
+        }
+        Ok(())
+    }
+
+    /// Create a completion queue builder.
+    pub fn builder() -> CqBuilder {
+        CqBuilder::new()
+    }
+
+    /// Create a new completion queue that ignores overruns.
+    /// See [`CqBuilder::ignore_overrun()`] for details.
+    #[cfg(mlnx5)]
+    pub(crate) fn new_ignore_overrun(
+        ctx: &Context,
+        capacity: u32,
+        comp_vector: u32,
+    ) -> Result<Cq, CqCreationError> {
+        let max_capacity = ctx.attr().max_cqe as u32;
+        if capacity > max_capacity {
+            return Err(CqCreationError::TooManyCqes(max_capacity));
+        }
+        Self::check_comp_vector(ctx, comp_vector)?;
+
+        let _guard = ctx.lock_creation();
+        let mut init_attr = ibv_cq_init_attr_ex {
+            cqe: capacity,
+            comp_vector,
+            comp_mask: ibv_cq_init_attr_mask::IBV_CQ_INIT_ATTR_MASK_FLAGS.0,
+            flags: ibv_create_cq_attr_flags::IBV_CREATE_CQ_ATTR_IGNORE_OVERRUN.0,
+            ..Default::default()
+        };
+        // SAFETY: FFI.
+        let cq = unsafe { ibv_create_cq_ex(ctx.as_raw(), &mut init_attr) };
+        let Some(cq) = NonNull::new(cq) else {
+            let err = IoError::last_os_error();
+            return Err(match err.raw_os_error() {
+                Some(libc::EOPNOTSUPP | libc::ENOSYS) => {
+                    CqCreationError::UnsupportedFeature("ignore_overrun")
+                }
+                _ => err.into(),
+            });
+        };
+        // `ibv_cq_ex` begins with the fields of `ibv_cq`.
+        let cq = IbvCq::from(cq.cast::<ibv_cq>());
+
+        let mut inner = CqInner::new(ctx, cq);
+        inner.ignore_overrun = true;
+        inner.comp_vector = comp_vector;
         Ok(Self {
-            inner: Arc::new(CqInner {
-                ctx: ctx.clone(),
-                cq,
-            }),
+            inner: Arc::new(inner),
             cq,
         })
     }
 
+    /// Create a new completion queue that ignores overruns.
+    /// This is unsupported on MLNX_OFED v4.x.
+    #[cfg(mlnx4)]
+    pub(crate) fn new_ignore_overrun(_: &Context, _: u32, _: u32) -> Result<Cq, CqCreationError> {
+        Err(CqCreationError::UnsupportedFeature("ignore_overrun"))
+    }
+
     /// Create a new completion queue with experimental features.
     #[cfg(mlnx4)]
     pub fn new_exp(ctx: &Context, capacity: u32) -> Result<Cq, CqCreationError> {
@@ -109,6 +267,7 @@ impl Cq {
             return Err(CqCreationError::TooManyCqes(max_capacity));
         }
 
+        let _guard = ctx.lock_creation();
         // SAFETY: FFI.
         let cq = unsafe {
             let mut init_attr = ibv_exp_cq_init_attr {
@@ -130,10 +289,7 @@ impl Cq {
         let cq = IbvCq::from(cq);
 
         Ok(Self {
-            inner: Arc::new(CqInner {
-                ctx: ctx.clone(),
-                cq,
-            }),
+            inner: Arc::new(CqInner::new(ctx, cq)),
             cq,
         })
     }
@@ -143,11 +299,71 @@ impl Cq {
         self.cq.as_ptr()
     }
 
+    /// Get the underlying [`ibv_cq`] pointer as [`NonNull`].
+    ///
+    /// The pointer is valid only while this CQ or any of its clones is
+    /// alive.
+    pub fn as_nonnull(&self) -> NonNull<ibv_cq> {
+        *self.cq
+    }
+
     /// Get the underlying [`Context`].
     pub fn context(&self) -> &Context {
         &self.inner.ctx
     }
 
+    /// Get the completion channel of the CQ, if it was created with
+    /// [`Cq::new_notifiable()`].
+    pub(crate) fn comp_channel(&self) -> Option<NonNull<ibv_comp_channel>> {
+        self.inner.channel
+    }
+
+    /// Request a completion event on the completion channel upon the next
+    /// completion, or upon the next solicited completion if `solicited_only`
+    /// is set. Only one event is generated per request.
+    ///
+    /// Completions that arrive before the request do not generate events, so
+    /// poll the CQ once more after requesting, before waiting for the event.
+    pub fn req_notify(&self, solicited_only: bool) -> io::Result<()> {
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_req_notify_cq(self.as_raw(), solicited_only as i32) };
+        from_c_ret(ret)
+    }
+
+    /// Block until a completion event requested by [`Cq::req_notify()`]
+    /// arrives on the completion channel, and acknowledge it.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the CQ was not created with [`Cq::new_notifiable()`].
+    pub fn wait_event(&self) -> io::Result<()> {
+        let channel = self.comp_channel().expect("CQ has no completion channel");
+        let mut cq = ptr::null_mut();
+        let mut cq_ctx = ptr::null_mut();
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_get_cq_event(channel.as_ptr(), &mut cq, &mut cq_ctx) };
+        if ret != 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        // SAFETY: FFI, and every event must be acknowledged.
+        unsafe { ibv_ack_cq_events(cq, 1) };
+        Ok(())
+    }
+
+    /// Return `true` if the CQ was created to ignore overruns, in which case
+    /// completions may be dropped when it is full.
+    #[inline]
+    pub fn ignores_overrun(&self) -> bool {
+        self.inner.ignore_overrun
+    }
+
+    /// Get the completion vector that the CQ reports events to.
+    #[inline]
+    pub fn comp_vector(&self) -> u32 {
+        self.inner.comp_vector
+    }
+
     /// Get the capacity of the completion queue.
     #[inline]
     pub fn capacity(&self) -> u32 {
@@ -155,6 +371,64 @@ impl Cq {
         (unsafe { (*self.cq.as_ptr()).cqe }) as u32
     }
 
+    /// Lock the deferred work completions.
+    fn lock_stash(&self) -> MutexGuard<'_, VecDeque<Wc>> {
+        // A panicking predicate of `poll_matching` or classifier of
+        // `poll_round_robin` does not leave the stash inconsistent.
+        self.inner
+            .stash
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Put work completions back into the CQ, so that subsequent polls
+    /// return them before any new completion.
+    pub(crate) fn stash(&self, wc: impl IntoIterator<Item = Wc>) {
+        let mut stash = self.lock_stash();
+        stash.extend(wc);
+        self.inner
+            .stashed
+            .store(!stash.is_empty(), Ordering::Release);
+    }
+
+    /// Poll at most `num` work completions into `wc`, returning stashed work
+    /// completions first if there are any. Return the value of `ibv_poll_cq`.
+    ///
+    /// # Safety
+    ///
+    /// `wc` must be valid for writing `num` work completions.
+    #[inline(always)]
+    unsafe fn poll_raw(&self, num: i32, wc: *mut Wc) -> i32 {
+        if self.inner.stashed.load(Ordering::Acquire) {
+            let mut stash = self.lock_stash();
+            let n = stash.len().min(num as usize);
+            for (i, entry) in stash.drain(..n).enumerate() {
+                ptr::write(wc.add(i), entry);
+            }
+            self.inner
+                .stashed
+                .store(!stash.is_empty(), Ordering::Release);
+            if n > 0 {
+                return n as i32;
+            }
+        }
+
+        // SAFETY: FFI, and that `Wc` is transparent over `ibv_wc`.
+        let polled = ibv_poll_cq(self.as_raw(), num, wc.cast());
+
+        #[cfg(feature = "tracing")]
+        for i in 0..polled.max(0) as usize {
+            let wc = &*wc.add(i);
+            trace_event!(
+                qpn = wc.qp_num(),
+                wr_id = wc.wr_id(),
+                status = ?wc.status(),
+                "completion"
+            );
+        }
+        polled
+    }
+
     /// Non-blockingly poll. Return the work completions polled.
     ///
     /// It is the caller's responsibility to check the status codes of the
@@ -180,8 +454,8 @@ impl Cq {
     pub fn poll_some(&self, num: u32) -> io::Result<Vec<Wc>> {
         let mut wc = <Vec<Wc>>::with_capacity(num as usize);
 
-        // SAFETY: FFI, and that `Wc` is transparent over `ibv_wc`.
-        let num = unsafe { ibv_poll_cq(self.as_raw(), wc.len() as i32, wc.as_mut_ptr().cast()) };
+        // SAFETY: the buffer has room for `num` work completions.
+        let num = unsafe { self.poll_raw(num as i32, wc.as_mut_ptr()) };
         if num >= 0 {
             unsafe { wc.set_len(num as usize) };
             Ok(wc)
@@ -201,8 +475,8 @@ impl Cq {
     #[inline(always)]
     pub fn poll_one(&self) -> io::Result<Option<Wc>> {
         let mut wc = <MaybeUninit<Wc>>::uninit();
-        // SAFETY: FFI
-        let num = unsafe { ibv_poll_cq(self.as_raw(), 1, wc.as_mut_ptr().cast()) };
+        // SAFETY: the buffer has room for one work completion.
+        let num = unsafe { self.poll_raw(1, wc.as_mut_ptr()) };
         if num >= 0 {
             Ok(if num == 0 {
                 None
@@ -230,8 +504,8 @@ impl Cq {
             return Ok(0);
         }
 
-        // SAFETY: FFI, and that `Wc` is transparent over `ibv_wc`.
-        let num = unsafe { ibv_poll_cq(self.as_raw(), wc.len() as i32, wc.as_mut_ptr().cast()) };
+        // SAFETY: the buffer has room for `wc.len()` work completions.
+        let num = unsafe { self.poll_raw(wc.len() as i32, wc.as_mut_ptr()) };
         if num >= 0 {
             Ok(num as u32)
         } else {
@@ -239,6 +513,158 @@ impl Cq {
         }
     }
 
+    /// Non-blockingly poll into the given possibly-uninitialized buffer.
+    /// Return the polled work completions as a slice borrowed from the buffer.
+    /// This method should be preferred over `poll_some` in hot loops to avoid
+    /// allocating and copying work completions, and is more ergonomic than
+    /// `poll_into` as the returned slice contains only the valid entries.
+    ///
+    /// It is the caller's responsibility to check the status codes of the
+    /// returned work completion entries.
+    #[inline]
+    pub fn poll_borrowed<'a>(&self, wc: &'a mut [MaybeUninit<Wc>]) -> io::Result<&'a [Wc]> {
+        if wc.is_empty() {
+            return Ok(&[]);
+        }
+
+        // SAFETY: the buffer has room for `wc.len()` work completions.
+        let num = unsafe { self.poll_raw(wc.len() as i32, wc.as_mut_ptr().cast()) };
+        if num >= 0 {
+            // SAFETY: the first `num` entries are initialized by `ibv_poll_cq`,
+            // and `MaybeUninit<Wc>` has the same layout as `Wc`.
+            Ok(unsafe { slice::from_raw_parts(wc.as_ptr().cast(), num as usize) })
+        } else {
+            Err(io::Error::from_raw_os_error(num))
+        }
+    }
+
+    /// Non-blockingly poll work completions that satisfy the predicate into
+    /// the given buffer. This is useful when the CQ is shared by multiple
+    /// subsystems, each recognizing its own completions, e.g., by a `wr_id`
+    /// range.
+    ///
+    /// Polled completions that do not satisfy the predicate are deferred in
+    /// an internal buffer. Subsequent polls of any kind, including other
+    /// `poll_matching` calls with different predicates, return deferred
+    /// completions before new ones.
+    ///
+    /// Return the number of matching work completions written to the front of
+    /// `wc`, and the number of work completions left deferred.
+    ///
+    /// It is the caller's responsibility to check the status codes of the
+    /// returned work completion entries.
+    pub fn poll_matching(
+        &self,
+        pred: impl Fn(&Wc) -> bool,
+        wc: &mut [Wc],
+    ) -> io::Result<(usize, usize)> {
+        let mut stash = self.lock_stash();
+        let mut matched = 0;
+        let mut i = 0;
+        let mut polled = false;
+        loop {
+            // Take matching completions, keeping the others in order.
+            while i < stash.len() && matched < wc.len() {
+                if pred(&stash[i]) {
+                    wc[matched] = stash.remove(i).unwrap();
+                    matched += 1;
+                } else {
+                    i += 1;
+                }
+            }
+            if polled || matched == wc.len() {
+                break;
+            }
+            polled = true;
+
+            let rest = &mut wc[matched..];
+            // SAFETY: FFI, and that `Wc` is transparent over `ibv_wc`. The
+            // stash is locked, so that deferred completions stay ahead of new
+            // ones for concurrent pollers.
+            let num =
+                unsafe { ibv_poll_cq(self.as_raw(), rest.len() as i32, rest.as_mut_ptr().cast()) };
+            if num < 0 {
+                return Err(io::Error::from_raw_os_error(num));
+            }
+
+            // Defer new completions before classifying them, so that none is
+            // lost if `pred` panics.
+            stash.extend(&rest[..num as usize]);
+            self.inner
+                .stashed
+                .store(!stash.is_empty(), Ordering::Release);
+        }
+
+        self.inner
+            .stashed
+            .store(!stash.is_empty(), Ordering::Release);
+        Ok((matched, stash.len()))
+    }
+
+    /// Non-blockingly poll all available work completions, and return at most
+    /// `max_per_stream` of them for each stream, as classified by `stream_of`.
+    /// This keeps one busy stream from starving the others when several
+    /// independent request streams share the CQ.
+    ///
+    /// The returned completions are in polling order. Completions beyond the
+    /// budget of their stream are deferred like in [`Cq::poll_matching()`],
+    /// and are returned by subsequent polls before new ones, so no stream
+    /// loses completions or sees them out of order.
+    ///
+    /// It is the caller's responsibility to check the status codes of the
+    /// returned work completion entries.
+    pub fn poll_round_robin<S: Eq + Hash>(
+        &self,
+        max_per_stream: usize,
+        stream_of: impl Fn(&Wc) -> S,
+    ) -> io::Result<Vec<Wc>> {
+        let mut stash = self.lock_stash();
+
+        // Poll in batches on the stack, up to the CQ capacity so that a busy
+        // CQ does not keep the loop going. New completions are deferred
+        // before they are classified, so that none is lost if `stream_of`
+        // panics.
+        let mut buf = [Wc::default(); ROUND_ROBIN_BATCH];
+        let mut total = 0;
+        while total < self.capacity() as usize {
+            // SAFETY: FFI, and that `Wc` is transparent over `ibv_wc`. The
+            // stash is locked, so that deferred completions stay ahead of new
+            // ones for concurrent pollers.
+            let num =
+                unsafe { ibv_poll_cq(self.as_raw(), buf.len() as i32, buf.as_mut_ptr().cast()) };
+            if num < 0 {
+                return Err(io::Error::from_raw_os_error(num));
+            }
+            stash.extend(&buf[..num as usize]);
+            self.inner
+                .stashed
+                .store(!stash.is_empty(), Ordering::Release);
+
+            total += num as usize;
+            if (num as usize) < buf.len() {
+                break;
+            }
+        }
+
+        let mut taken = HashMap::new();
+        let mut wcs = Vec::new();
+        stash.retain(|wc| {
+            let count = taken.entry(stream_of(wc)).or_insert(0usize);
+            if *count < max_per_stream {
+                *count += 1;
+                wcs.push(*wc);
+                false
+            } else {
+                true
+            }
+        });
+
+        self.inner
+            .stashed
+            .store(!stash.is_empty(), Ordering::Release);
+        Ok(wcs)
+    }
+
     /// Non-blockingly poll one work completion into the given work completion.
     /// Return the number of work completions polled.
     /// This method should be preferred over `poll_into` when you only have one
@@ -252,8 +678,8 @@ impl Cq {
     /// work completion is not guaranteed.
     #[inline(always)]
     pub fn poll_one_into(&self, wc: &mut Wc) -> io::Result<u32> {
-        // SAFETY: FFI
-        let num = unsafe { ibv_poll_cq(self.as_raw(), 1, (wc as *mut Wc).cast()) };
+        // SAFETY: the buffer has room for one work completion.
+        let num = unsafe { self.poll_raw(1, wc) };
         if num >= 0 {
             Ok(num as u32)
         } else {
@@ -320,6 +746,28 @@ impl Cq {
         Ok(unsafe { wc.assume_init() })
     }
 
+    /// Blockingly poll one work completion, until one arrives or `cancel` is
+    /// set. Return `None` if cancelled.
+    ///
+    /// The flag is checked once per poll attempt, so a polling thread returns
+    /// shortly after another thread sets it. A completion that is already
+    /// available is returned even if cancellation has been requested.
+    ///
+    /// It is the caller's responsibility to check the status codes of the
+    /// returned work completion entry.
+    pub fn poll_one_blocking_cancellable(&self, cancel: &AtomicBool) -> io::Result<Option<Wc>> {
+        let mut wc = Wc::default();
+        loop {
+            if self.poll_one_into(&mut wc)? > 0 {
+                return Ok(Some(wc));
+            }
+            if cancel.load(Ordering::Acquire) {
+                return Ok(None);
+            }
+            std::hint::spin_loop();
+        }
+    }
+
     /// Blockingly wait until a work completion occurs and consume that
     /// work request.
     ///
@@ -328,16 +776,16 @@ impl Cq {
     /// Panic if the work completion status is not success.
     pub fn poll_one_blocking_consumed(&self) {
         #[cfg(not(feature = "warned_spin"))]
-        fn do_poll(cq: *mut ibv_cq, wc: &mut MaybeUninit<Wc>) {
-            while unsafe { ibv_poll_cq(cq, 1, wc as *mut _ as _) } == 0 {}
+        fn do_poll(cq: &Cq, wc: &mut MaybeUninit<Wc>) {
+            while unsafe { cq.poll_raw(1, wc.as_mut_ptr()) } == 0 {}
         }
 
         #[cfg(feature = "warned_spin")]
-        fn do_poll(cq: *mut ibv_cq, wc: &mut MaybeUninit<Wc>) {
+        fn do_poll(cq: &Cq, wc: &mut MaybeUninit<Wc>) {
             use quanta::Instant;
 
             let mut last_warn = Instant::now();
-            while unsafe { ibv_poll_cq(cq, 1, wc as *mut _ as _) } == 0 {
+            while unsafe { cq.poll_raw(1, wc.as_mut_ptr()) } == 0 {
                 if last_warn.elapsed().as_secs() >= 1 {
                     eprintln!("warning: spinning on CQ poll ...");
                     let bt = std::backtrace::Backtrace::capture();
@@ -352,7 +800,7 @@ impl Cq {
 
         // SAFETY: `Wc` is transparent over `ibv_wc`.
         let mut wc = <MaybeUninit<Wc>>::uninit();
-        do_poll(self.as_raw(), &mut wc);
+        do_poll(self, &mut wc);
 
         // SAFETY: `wc` is initialized by `ibv_poll_cq`.
         assert_eq!(unsafe { wc.assume_init() }.status(), WcStatus::Success);
@@ -456,4 +904,13 @@ pub enum CqCreationError {
     /// capacity, which is contained in the error.
     #[error("CQ capacity too large (maximum: {0})")]
     TooManyCqes(u32),
+
+    /// The completion vector does not exist on the device. Contains the
+    /// requested vector and the number of vectors of the device.
+    #[error("completion vector {0} out of range (available: {1})")]
+    InvalidCompVector(u32, u32),
+
+    /// The requested feature is not supported by the library or the device.
+    #[error("unsupported CQ feature: {0}")]
+    UnsupportedFeature(&'static str),
 }
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::os::fd::RawFd;
+use std::pin::Pin;
+use std::ptr::{self, NonNull};
+use std::task::{ready, Context as TaskContext, Poll};
+
+use futures_core::Stream;
+use tokio::io::unix::AsyncFd;
+
+use super::{Cq, Wc};
+use crate::bindings::*;
+use crate::rdma::qp::{Qp, SqAccounting};
+
+/// Maximum number of work completions to poll from the CQ at a time.
+const POLL_BATCH: usize = 16;
+
+/// Stream of work completions of a CQ, driven by its completion channel.
+/// Created by [`Cq::into_stream()`].
+///
+/// The stream only polls the CQ when the consumer asks for the next item, and
+/// sleeps on the completion channel when the CQ is empty. Completions are
+/// therefore consumed at the pace of the consumer, never buffered beyond one
+/// small batch.
+///
+/// The stream never ends by itself. It yields an error if polling the CQ or
+/// waiting on the channel fails; it is the caller's responsibility to check
+/// the status codes of the yielded work completions.
+pub struct CqStream {
+    /// Completion channel of the CQ, registered with the tokio reactor.
+    /// Deregistered before the CQ, which may close the channel, is dropped.
+    channel: AsyncFd<RawFd>,
+
+    cq: Cq,
+
+    /// Whether a completion notification has been requested and not yet
+    /// received.
+    armed: bool,
+
+    /// Work completions polled but not yet yielded.
+    polled: VecDeque<Wc>,
+
+    /// QPs whose send completions are accounted, indexed by QP number.
+    accounting: HashMap<u32, SqAccounting>,
+}
+
+impl CqStream {
+    fn new(cq: Cq) -> io::Result<Self> {
+        let channel = cq.comp_channel().ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidInput,
+                "CQ has no completion channel; create it with `Cq::new_notifiable`",
+            )
+        })?;
+
+        // SAFETY: the channel is valid as long as the CQ is alive.
+        let fd = unsafe { (*channel.as_ptr()).fd };
+        // SAFETY: FFI.
+        let ret = unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+        };
+        if ret < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(Self {
+            channel: AsyncFd::new(fd)?,
+            cq,
+            armed: false,
+            polled: VecDeque::with_capacity(POLL_BATCH),
+            accounting: HashMap::new(),
+        })
+    }
+
+    /// Get the CQ of the stream.
+    #[inline]
+    pub fn cq(&self) -> &Cq {
+        &self.cq
+    }
+
+    /// Account the send completions of the given QP with
+    /// [`Qp::account_send()`] as they are yielded, so that
+    /// [`Qp::sq_depth_in_use()`] stays accurate. The QP must post its send
+    /// work requests to this CQ and have a different receive CQ. The stream
+    /// does not keep the QP alive.
+    pub fn account_sends_of(&mut self, qp: &Qp) -> &mut Self {
+        self.accounting.insert(qp.qp_num(), qp.sq_accounting());
+        self
+    }
+
+    /// Try to get a completion event from the channel without blocking.
+    /// Return `false` if there is none.
+    fn get_event(&self, channel: NonNull<ibv_comp_channel>) -> io::Result<bool> {
+        let mut cq = ptr::null_mut();
+        let mut cq_ctx = ptr::null_mut();
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_get_cq_event(channel.as_ptr(), &mut cq, &mut cq_ctx) };
+        if ret != 0 {
+            let err = IoError::last_os_error();
+            return match err.kind() {
+                IoErrorKind::WouldBlock => Ok(false),
+                _ => Err(err),
+            };
+        }
+
+        // SAFETY: FFI, and every event must be acknowledged.
+        unsafe { ibv_ack_cq_events(cq, 1) };
+        Ok(true)
+    }
+}
+
+impl Stream for CqStream {
+    type Item = io::Result<Wc>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(wc) = this.polled.pop_front() {
+                if let Some(accounting) = this.accounting.get(&wc.qp_num()) {
+                    accounting.account(&wc);
+                }
+                return Poll::Ready(Some(Ok(wc)));
+            }
+
+            let mut wc = [Wc::default(); POLL_BATCH];
+            match this.cq.poll_into(&mut wc) {
+                Ok(0) => {}
+                Ok(n) => {
+                    this.polled.extend(&wc[..n as usize]);
+                    continue;
+                }
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+
+            // Arm the CQ and poll once more, as completions that arrived
+            // before arming do not generate events.
+            if !this.armed {
+                if let Err(e) = this.cq.req_notify(false) {
+                    return Poll::Ready(Some(Err(e)));
+                }
+                this.armed = true;
+                continue;
+            }
+
+            let mut guard = match ready!(this.channel.poll_read_ready(cx)) {
+                Ok(guard) => guard,
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            };
+            match this.get_event(this.cq.comp_channel().unwrap()) {
+                Ok(true) => this.armed = false,
+                Ok(false) => guard.clear_ready(),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+impl Cq {
+    /// Convert the CQ into an asynchronous stream of its work completions.
+    ///
+    /// The CQ must have been created with [`Cq::new_notifiable()`], and the
+    /// stream must be polled within a tokio runtime with I/O enabled. Other
+    /// handles of the CQ should not poll it or wait on its channel while the
+    /// stream is in use.
+    pub fn into_stream(self) -> io::Result<CqStream> {
+        CqStream::new(self)
+    }
+}
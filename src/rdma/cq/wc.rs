@@ -1,4 +1,5 @@
-use std::{fmt, hint, mem};
+use std::io::{self, ErrorKind as IoErrorKind};
+use std::{fmt, mem};
 
 use thiserror::Error;
 
@@ -8,21 +9,30 @@ use crate::bindings::*;
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum WcOpcode {
     /// Send request.
-    Send = ibv_wc_opcode::IBV_WC_SEND as _,
+    Send,
     /// RDMA write request.
-    RdmaWrite = ibv_wc_opcode::IBV_WC_RDMA_WRITE as _,
+    RdmaWrite,
     /// RDMA read request.
-    RdmaRead = ibv_wc_opcode::IBV_WC_RDMA_READ as _,
+    RdmaRead,
     /// RDMA compare-and-swap request.
-    CompSwap = ibv_wc_opcode::IBV_WC_COMP_SWAP as _,
+    CompSwap,
     /// RDMA fetch-and-add request.
-    FetchAdd = ibv_wc_opcode::IBV_WC_FETCH_ADD as _,
+    FetchAdd,
     /// Memory window bind request.
-    BindMw = ibv_wc_opcode::IBV_WC_BIND_MW as _,
+    BindMw,
+    /// Local invalidate request.
+    #[cfg(mlnx5)]
+    LocalInv,
+    /// TCP segmentation offload (TSO) send request.
+    #[cfg(mlnx5)]
+    TsoSend,
     /// Receive request.
-    Recv = ibv_wc_opcode::IBV_WC_RECV as _,
+    Recv,
     /// Receive request with immediate data.
-    RecvRdmaImm = ibv_wc_opcode::IBV_WC_RECV_RDMA_WITH_IMM as _,
+    RecvRdmaImm,
+    /// An opcode that this crate does not recognize, which may be reported
+    /// by newer drivers. Contains the raw opcode value.
+    Unknown(u32),
 }
 
 impl From<u32> for WcOpcode {
@@ -34,9 +44,13 @@ impl From<u32> for WcOpcode {
             ibv_wc_opcode::IBV_WC_COMP_SWAP => WcOpcode::CompSwap,
             ibv_wc_opcode::IBV_WC_FETCH_ADD => WcOpcode::FetchAdd,
             ibv_wc_opcode::IBV_WC_BIND_MW => WcOpcode::BindMw,
+            #[cfg(mlnx5)]
+            ibv_wc_opcode::IBV_WC_LOCAL_INV => WcOpcode::LocalInv,
+            #[cfg(mlnx5)]
+            ibv_wc_opcode::IBV_WC_TSO => WcOpcode::TsoSend,
             ibv_wc_opcode::IBV_WC_RECV => WcOpcode::Recv,
             ibv_wc_opcode::IBV_WC_RECV_RDMA_WITH_IMM => WcOpcode::RecvRdmaImm,
-            _ => panic!("invalid opcode: {}", wc_opcode),
+            _ => WcOpcode::Unknown(wc_opcode),
         }
     }
 }
@@ -205,6 +219,11 @@ pub enum WcStatus {
     #[cfg(mlnx5)]
     #[error("rendezvous request tagged buffer insufficient")]
     TmRndvIncomplete = ibv_wc_status::IBV_WC_TM_RNDV_INCOMPLETE as _,
+
+    /// **Unknown Status:** a status code that this crate does not recognize,
+    /// which may be reported by newer drivers. Contains the raw status code.
+    #[error("unknown status code {0}")]
+    Unknown(u32),
 }
 
 #[cfg(mlnx4)]
@@ -214,22 +233,48 @@ const WCSTATUS_UPPER_BOUND: u32 = ibv_wc_status::IBV_WC_GENERAL_ERR;
 const WCSTATUS_UPPER_BOUND: u32 = ibv_wc_status::IBV_WC_TM_RNDV_INCOMPLETE;
 
 impl WcStatus {
-    /// Cast `ibv_wc_status` into `WcStatus`.
+    /// Cast `ibv_wc_status` into `WcStatus`, assuming that it is a known
+    /// status code.
+    ///
+    /// # Safety
+    ///
+    /// `wc_status` must not exceed `WCSTATUS_UPPER_BOUND`.
     #[inline]
     pub(crate) unsafe fn from_unsafe(wc_status: ibv_wc_status::Type) -> Self {
-        match wc_status {
-            x if x <= WCSTATUS_UPPER_BOUND => mem::transmute(x),
-            _ => hint::unreachable_unchecked(),
-        }
+        // `#[repr(u32)]` lays out a field-less variant as its `u32` tag padded
+        // to the size of the enum (RFC 2195), and valid status codes in
+        // `ibv_wc_status` are contiguous.
+        debug_assert!(wc_status <= WCSTATUS_UPPER_BOUND);
+        mem::transmute::<[u32; 2], Self>([wc_status, 0])
+    }
+}
+
+impl From<WcStatus> for io::Error {
+    /// Convert a work completion status into an I/O error whose kind reflects
+    /// the cause, e.g., `TimedOut` for retry counter exhaustion, so that
+    /// callers can react to it without matching on the status itself.
+    fn from(status: WcStatus) -> Self {
+        let kind = match status {
+            WcStatus::RetryExcErr | WcStatus::RnrRetryExcErr | WcStatus::RespTimeoutErr => {
+                IoErrorKind::TimedOut
+            }
+            WcStatus::RemAccessErr | WcStatus::LocProtErr | WcStatus::LocAccessErr => {
+                IoErrorKind::PermissionDenied
+            }
+            WcStatus::WrFlushErr => IoErrorKind::Interrupted,
+            WcStatus::LocLenErr | WcStatus::RemInvReqErr => IoErrorKind::InvalidInput,
+            _ => IoErrorKind::Other,
+        };
+        io::Error::new(kind, status)
     }
 }
 
 impl From<u32> for WcStatus {
     fn from(wc_status: u32) -> Self {
         match wc_status {
-            // SAFETY: Valid status codes in `ibv_wc_status` are contiguous.
-            x if x <= WCSTATUS_UPPER_BOUND => unsafe { mem::transmute(x) },
-            x => panic!("invalid wc status: {}", x),
+            // SAFETY: the status code is known.
+            x if x <= WCSTATUS_UPPER_BOUND => unsafe { Self::from_unsafe(x) },
+            x => Self::Unknown(x),
         }
     }
 }
@@ -253,10 +298,23 @@ impl Wc {
     }
 
     /// Get the completion status.
+    /// Status codes unknown to this crate are reported as [`WcStatus::Unknown`].
     #[inline]
     pub fn status(&self) -> WcStatus {
-        // SAFETY: SAFETY: enum constraints of `libibverbs`.
-        unsafe { WcStatus::from_unsafe(self.0.status) }
+        WcStatus::from(self.0.status)
+    }
+
+    /// Get the completion status, without checking whether the status code
+    /// is known to this crate.
+    ///
+    /// # Safety
+    ///
+    /// The status code must be one of the variants of [`WcStatus`] other than
+    /// [`WcStatus::Unknown`], which is the case with drivers that report no
+    /// status codes newer than this crate.
+    #[inline]
+    pub unsafe fn status_unchecked(&self) -> WcStatus {
+        WcStatus::from_unsafe(self.0.status)
     }
 
     /// Get the completion status as a `Result`.
@@ -277,12 +335,51 @@ impl Wc {
         WcOpcode::from(self.0.opcode)
     }
 
+    /// Get the number of the local QP that the work request was posted to.
+    #[inline]
+    pub fn qp_num(&self) -> u32 {
+        self.0.qp_num
+    }
+
     /// Get the number of bytes processed or transferred.
     #[inline]
     pub fn byte_len(&self) -> usize {
         self.0.byte_len as usize
     }
 
+    /// Return `true` if this is a completion of a receive work request, i.e.,
+    /// of an incoming send or RDMA write with immediate.
+    ///
+    /// The opcode of erroneous completions is undefined, so check the status
+    /// first.
+    #[inline]
+    pub fn is_recv(&self) -> bool {
+        matches!(self.opcode(), WcOpcode::Recv | WcOpcode::RecvRdmaImm)
+    }
+
+    /// Return `true` if this is a receive completion of an incoming RDMA write
+    /// with immediate, whose data went to the memory region targeted by the
+    /// writer instead of the receive buffer.
+    #[inline]
+    pub fn is_recv_imm(&self) -> bool {
+        self.opcode() == WcOpcode::RecvRdmaImm
+    }
+
+    /// Get the number of bytes placed in the receive buffer of a receive
+    /// completion, i.e., the length of an incoming send, or zero for an
+    /// incoming RDMA write with immediate.
+    ///
+    /// Unlike [`Wc::byte_len()`], which reports the length of the RDMA write
+    /// in the latter case, this is safe to use for reading the receive buffer.
+    #[inline]
+    pub fn received_bytes(&self) -> usize {
+        if self.is_recv_imm() {
+            0
+        } else {
+            self.byte_len()
+        }
+    }
+
     /// Get the immediate data.
     #[inline]
     pub fn imm(&self) -> Option<u32> {
@@ -299,6 +396,25 @@ impl Wc {
     pub fn imm_unchecked(&self) -> u32 {
         self.0.imm()
     }
+
+    /// Check the statuses of a batch of work completions, e.g., as returned
+    /// by [`Cq::poll()`](super::Cq::poll). Return the index and status of the
+    /// first failed one, if any.
+    pub fn check_all(wcs: &[Wc]) -> Result<(), (usize, WcStatus)> {
+        match wcs.iter().position(|wc| wc.status() != WcStatus::Success) {
+            Some(i) => Err((i, wcs[i].status())),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the statuses of a batch of work completions, and return the
+    /// indices and statuses of all failed ones, in order.
+    pub fn check_all_collect(wcs: &[Wc]) -> Vec<(usize, WcStatus)> {
+        wcs.iter()
+            .enumerate()
+            .filter_map(|(i, wc)| wc.ok().err().map(|status| (i, status)))
+            .collect()
+    }
 }
 
 impl Default for Wc {
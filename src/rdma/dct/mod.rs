@@ -50,6 +50,11 @@ pub enum DctCreationError {
     /// DCT state is not active.
     #[error("DCT is not in active state")]
     NotActive,
+
+    /// The builder options are inconsistent with each other or with the
+    /// given resources. The field explains the problem.
+    #[error("invalid configuration: {0}")]
+    InvalidConfiguration(String),
 }
 
 /// Ownership holder of the DCT.
@@ -93,9 +98,27 @@ impl Dct {
         &self.inner.init_attr
     }
 
+    /// Check whether the PD and the CQ belong to the given device context,
+    /// which would otherwise fail the creation with an obscure error.
+    fn check_config(ctx: &Context, builder: &DctBuilder) -> Result<(), DctCreationError> {
+        let pd_ctx = builder.pd.map(|pd| pd.context().as_raw());
+        let cq_ctx = builder.cq.map(|cq| cq.context().as_raw());
+        for (name, res_ctx) in [("PD", pd_ctx), ("CQ", cq_ctx)] {
+            if res_ctx.is_some_and(|res_ctx| res_ctx != ctx.as_raw()) {
+                return Err(DctCreationError::InvalidConfiguration(format!(
+                    "{} belongs to a different device context than the DCT",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Create a new DCT.
     pub(crate) fn new(ctx: &Context, builder: DctBuilder) -> Result<Self, DctCreationError> {
+        Self::check_config(ctx, &builder)?;
         let init_attr = builder.unwrap()?;
+        let _guard = ctx.lock_creation();
         let dct = {
             let mut init_attr = init_attr.to_init_attr();
             // SAFETY: FFI.
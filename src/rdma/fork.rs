@@ -0,0 +1,51 @@
+//! Fork safety.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use crate::bindings::*;
+use crate::utils::interop::from_c_ret;
+
+/// Whether to initialize fork safety when a device is opened.
+static AUTO_FORK_INIT: AtomicBool = AtomicBool::new(true);
+
+/// Return value of `ibv_fork_init`, which is called at most once.
+static FORK_INIT_RET: OnceLock<i32> = OnceLock::new();
+
+/// Initialize `libibverbs` to support `fork()`.
+///
+/// Without this, memory registered as MRs is shared copy-on-write with forked
+/// children, and the parent's RDMA operations may silently read or write the
+/// child's copy of the pages instead of its own. `ibv_fork_init` makes
+/// registered memory unavailable to children to avoid this. On recent kernels
+/// that keep pinned pages with the parent, it does nothing.
+///
+/// This function must be called before any MR is registered, and has no
+/// effect after the first call. By default, it is called automatically
+/// when a device is opened for the first time; see [`set_auto_fork_init()`].
+pub fn fork_init() -> io::Result<()> {
+    // SAFETY: FFI.
+    let ret = *FORK_INIT_RET.get_or_init(|| unsafe { ibv_fork_init() });
+    from_c_ret(ret)
+}
+
+/// Set whether to call [`fork_init()`] automatically when a device is opened.
+/// Enabled by default.
+///
+/// Applications that never fork may disable it before opening any device,
+/// to avoid the overhead of making registered memory unavailable to children
+/// on older kernels.
+pub fn set_auto_fork_init(enabled: bool) {
+    AUTO_FORK_INIT.store(enabled, Ordering::Relaxed);
+}
+
+/// Call [`fork_init()`] if automatic initialization is enabled. Failures are
+/// logged instead of reported, as fork safety is best-effort in this case.
+pub(crate) fn auto_fork_init() {
+    if AUTO_FORK_INIT.load(Ordering::Relaxed) {
+        if let Err(e) = fork_init() {
+            log::warn!("cannot initialize fork safety: {}", e);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::net::Ipv6Addr;
 
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
 
 use crate::bindings::*;
 
@@ -14,6 +17,14 @@ pub struct Gid(pub ibv_gid);
 unsafe impl Send for Gid {}
 unsafe impl Sync for Gid {}
 
+#[cfg(feature = "zeroize")]
+impl Zeroize for Gid {
+    fn zeroize(&mut self) {
+        // SAFETY: all variants of the union are plain bytes.
+        unsafe { self.0.raw.zeroize() };
+    }
+}
+
 impl fmt::Debug for Gid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let gid = Ipv6Addr::from(*self);
@@ -38,6 +49,62 @@ impl PartialEq for Gid {
 
 impl Eq for Gid {}
 
+impl Hash for Gid {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // SAFETY: byte-level reinterpretation of POD union.
+        unsafe { self.0.raw }.hash(state)
+    }
+}
+
+impl Gid {
+    /// The subnet prefix of link-local GIDs, i.e., `fe80::/64`.
+    pub const LINK_LOCAL_PREFIX: u64 = 0xfe80_0000_0000_0000;
+
+    /// Get the raw bytes of the GID.
+    #[inline]
+    fn octets(&self) -> [u8; 16] {
+        // SAFETY: byte-level reinterpretation of POD union.
+        unsafe { self.0.raw }
+    }
+
+    /// Get the subnet prefix, i.e., the upper 64 bits of the GID in host
+    /// byte order.
+    #[inline]
+    pub fn subnet_prefix(&self) -> u64 {
+        u64::from_be_bytes(self.octets()[..8].try_into().unwrap())
+    }
+
+    /// Get the interface ID, i.e., the lower 64 bits of the GID in host byte
+    /// order. For InfiniBand port GIDs, this is the port GUID.
+    #[inline]
+    pub fn interface_id(&self) -> u64 {
+        u64::from_be_bytes(self.octets()[8..].try_into().unwrap())
+    }
+
+    /// Return `true` if the GID has the link-local subnet prefix `fe80::/64`.
+    ///
+    /// InfiniBand ports use this prefix unless the subnet manager assigns
+    /// another one; RoCE GIDs derived from the interface's link-local IPv6
+    /// address also have it.
+    #[inline]
+    pub fn is_link_local(&self) -> bool {
+        self.subnet_prefix() == Self::LINK_LOCAL_PREFIX
+    }
+
+    /// Return `true` if the GID has the same subnet prefix as `other`.
+    ///
+    /// On InfiniBand, endpoints in the same subnet are reachable by LID alone,
+    /// while endpoints in different subnets need global routing through a
+    /// router, i.e., QPs created with
+    /// [`QpBuilder::global_routing()`](crate::rdma::qp::QpBuilder::global_routing)
+    /// set.
+    #[inline]
+    pub fn same_subnet(&self, other: &Gid) -> bool {
+        self.subnet_prefix() == other.subnet_prefix()
+    }
+}
+
 impl From<ibv_gid> for Gid {
     #[inline]
     fn from(gid: ibv_gid) -> Self {
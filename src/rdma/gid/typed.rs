@@ -169,6 +169,32 @@ impl GidTyped {
     }
 }
 
+/// GID table entry of a port, with the information needed to decide whether
+/// and how the GID can be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GidEntry {
+    /// The GID.
+    pub gid: Gid,
+
+    /// Type of the GID.
+    pub gid_type: GidType,
+
+    /// Name of the network device that the GID is associated with, if any.
+    /// RoCE GIDs are usable only when they have an associated network device.
+    pub netdev: Option<String>,
+
+    /// Index of the GID in the port's GID table.
+    pub index: GidIndex,
+}
+
+impl GidEntry {
+    /// Return `true` if the GID is usable, i.e., it is an Infiniband GID, or a
+    /// RoCE GID associated with a network device.
+    pub fn is_usable(&self) -> bool {
+        self.gid_type.is_infiniband() || self.netdev.is_some()
+    }
+}
+
 impl PartialEq for GidTyped {
     #[inline]
     fn eq(&self, other: &Self) -> bool {
@@ -3,6 +3,7 @@
 pub mod context;
 pub mod cq;
 pub mod dct;
+pub mod fork;
 pub mod gid;
 pub mod mr;
 pub mod nic;
@@ -1,20 +1,28 @@
 //! Memory region (local and remote) and related types.
 
 mod mr_slice;
+mod mw;
 mod perm;
 mod remote;
 mod slicing;
 
-use std::io::{self, Error as IoError};
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::ops::RangeBounds;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::{fmt, slice};
+#[cfg(mlnx5)]
+use std::{mem, ptr};
 
 pub use self::mr_slice::*;
+pub use self::mw::*;
 pub use self::perm::*;
 pub use self::remote::*;
 pub use self::slicing::*;
 use super::pd::Pd;
+#[cfg(mlnx5)]
+use super::{qp::Qp, type_alias::WrId};
 use crate::bindings::*;
 use crate::utils::interop::from_c_ret;
 
@@ -65,15 +73,40 @@ impl IbvMr {
 impl_ibv_wrapper_traits!(ibv_mr, IbvMr);
 
 /// Ownership holder of memory region.
-struct MrInner {
+pub(crate) struct MrInner {
     pd: Pd,
     mr: IbvMr,
+
+    /// Whether the MR has been deregistered by [`Pd::dereg_all()`].
+    dereged: AtomicBool,
+
+    /// Whether the MR was registered with [`Permission::ZERO_BASED`].
+    zero_based: bool,
+}
+
+impl MrInner {
+    /// Deregister the MR if it has not been deregistered yet.
+    ///
+    /// # Safety
+    ///
+    /// The MR must not be used anymore, except being dropped.
+    pub(crate) unsafe fn dereg_once(&self) -> io::Result<()> {
+        if self.dereged.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        // SAFETY: guarded by `dereged`, so called only once.
+        self.mr.dereg()
+    }
 }
 
 impl Drop for MrInner {
     fn drop(&mut self) {
-        // SAFETY: call only once, and no UAF since I will be dropped.
-        unsafe { self.mr.dereg() }.expect("cannot dereg MR on drop");
+        // Failing to deregister leaks the MR, which is preferable to aborting
+        // while unwinding or tearing down.
+        // SAFETY: no UAF since I will be dropped.
+        if let Err(e) = unsafe { self.dereg_once() } {
+            log::warn!("cannot dereg MR {:p} on drop: {}", self.mr.as_ptr(), e);
+        }
     }
 }
 
@@ -109,16 +142,271 @@ impl Mr {
     /// invalid requests. However, if the registered address range is valid but
     /// not what the user wants, one-sided RDMA requests from the remote can
     /// unexpectedly modify the memory, leading to undefined behavior.
+    ///
+    /// Return an `InvalidInput` error if `len` is zero, as drivers differ in
+    /// whether they accept zero-length registrations.
+    ///
+    /// If `perm` contains [`Permission::ZERO_BASED`], the memory region is
+    /// addressed by offsets on the device. See [`Mr::as_remote()`] for
+    /// details.
     pub unsafe fn reg(pd: &Pd, buf: *mut u8, len: usize, perm: Permission) -> io::Result<Self> {
-        // SAFETY: FFI.
-        let mr = unsafe { ibv_reg_mr(pd.as_raw(), buf as _, len, perm.into()) };
+        if len == 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "zero-length memory regions are disallowed",
+            ));
+        }
+
+        let zero_based = perm.contains(Permission::ZERO_BASED);
+        let _guard = pd.context().lock_creation();
+        let mr = Self::do_reg(pd, buf, len, perm);
         let mr = NonNull::new(mr).ok_or_else(IoError::last_os_error)?;
         let mr = IbvMr::from(mr);
 
-        Ok(Self {
-            inner: Arc::new(MrInner { pd: pd.clone(), mr }),
+        let inner = Arc::new(MrInner {
+            pd: pd.clone(),
             mr,
-        })
+            dereged: AtomicBool::new(false),
+            zero_based,
+        });
+        pd.track_mr(&inner);
+        Ok(Self { inner, mr })
+    }
+
+    /// Call the registration verb.
+    ///
+    /// rdma-core expresses zero-based registration as an I/O virtual address
+    /// of zero, and rejects the access flag itself.
+    #[cfg(mlnx5)]
+    unsafe fn do_reg(pd: &Pd, buf: *mut u8, len: usize, perm: Permission) -> *mut ibv_mr {
+        if perm.contains(Permission::ZERO_BASED) {
+            let perm = perm - Permission::ZERO_BASED;
+            // SAFETY: FFI.
+            unsafe { ibv_reg_mr_iova(pd.as_raw(), buf as _, len, 0, perm.into()) }
+        } else {
+            // SAFETY: FFI.
+            unsafe { ibv_reg_mr(pd.as_raw(), buf as _, len, perm.into()) }
+        }
+    }
+
+    /// Call the registration verb.
+    ///
+    /// MLNX_OFED v4.x takes the zero-based access flag as is.
+    #[cfg(mlnx4)]
+    unsafe fn do_reg(pd: &Pd, buf: *mut u8, len: usize, perm: Permission) -> *mut ibv_mr {
+        // SAFETY: FFI.
+        unsafe { ibv_reg_mr(pd.as_raw(), buf as _, len, perm.into()) }
+    }
+
+    /// Register a memory region on the given buffer.
+    ///
+    /// Return an `InvalidInput` error if the buffer is empty.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must outlive the returned memory region and all its clones,
+    /// as it can be accessed by the device, and by remote peers if `perm`
+    /// allows, until the memory region is deregistered.
+    pub unsafe fn reg_slice(pd: &Pd, buf: &mut [u8], perm: Permission) -> io::Result<Self> {
+        Self::reg(pd, buf.as_mut_ptr(), buf.len(), perm)
+    }
+
+    /// Register a memory region intended as the target of RDMA atomics.
+    ///
+    /// RDMA atomic operations require their 8-byte operands to be naturally
+    /// aligned. This method returns an error if the base address is not 8-byte
+    /// aligned or the length is not a multiple of 8, instead of letting every
+    /// atomic operation on the memory region fail later.
+    ///
+    /// # Safety
+    ///
+    /// See [`Mr::reg()`].
+    pub unsafe fn reg_atomic(
+        pd: &Pd,
+        buf: *mut u8,
+        len: usize,
+        perm: Permission,
+    ) -> io::Result<Self> {
+        if buf as usize % 8 != 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                format!("atomic MR base address {:p} is not 8-byte aligned", buf),
+            ));
+        }
+        if len % 8 != 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                format!("atomic MR length {} is not a multiple of 8", len),
+            ));
+        }
+        Self::reg(pd, buf, len, perm)
+    }
+
+    /// The minimum length of memory regions created by [`Mr::reg_or_split()`].
+    pub const MIN_SPLIT_LEN: usize = 1 << 21;
+
+    /// Register memory regions on the given range of virtual memory, splitting
+    /// the range into multiple adjacent memory regions if registering it as a
+    /// whole fails with `ENOMEM`.
+    ///
+    /// Registering a very large range may fail because the kernel cannot pin
+    /// that many pages at once. In that case, this method repeatedly halves the
+    /// chunk size and retries, until every chunk is registered or the chunk
+    /// size drops to [`Mr::MIN_SPLIT_LEN`]. The returned memory regions are
+    /// ordered by address; use [`Mr::locate()`] to find the one covering a
+    /// given offset.
+    ///
+    /// Fail with `InvalidInput` if `len` is zero.
+    ///
+    /// # Safety
+    ///
+    /// See [`Mr::reg()`].
+    pub unsafe fn reg_or_split(
+        pd: &Pd,
+        buf: *mut u8,
+        len: usize,
+        perm: Permission,
+    ) -> io::Result<Vec<Self>> {
+        const PAGE_SIZE: usize = 4096;
+
+        if len == 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "zero-length memory regions are disallowed",
+            ));
+        }
+
+        let mut chunk = len;
+        loop {
+            let mrs = (0..len)
+                .step_by(chunk)
+                .map(|off| Self::reg(pd, buf.add(off), chunk.min(len - off), perm))
+                .collect::<io::Result<Vec<_>>>();
+            match mrs {
+                Err(e) if e.kind() == IoErrorKind::OutOfMemory && chunk > Self::MIN_SPLIT_LEN => {
+                    chunk = (chunk / 2 + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+                    chunk = chunk.max(Self::MIN_SPLIT_LEN);
+                }
+                ret => return ret,
+            }
+        }
+    }
+
+    /// Find the memory region that covers the given offset within a range
+    /// registered by [`Mr::reg_or_split()`]. Return the memory region and the
+    /// offset within it, or `None` if the offset is out of bounds.
+    pub fn locate(mrs: &[Self], offset: usize) -> Option<(&Self, usize)> {
+        let base = mrs.first()?.addr() as usize;
+        let addr = base.checked_add(offset)?;
+        let idx = mrs.partition_point(|mr| mr.addr() as usize + mr.len() <= addr);
+        let mr = mrs.get(idx)?;
+        Some((mr, addr - mr.addr() as usize))
+    }
+
+    /// Register a new memory region on a sub-range of this memory region,
+    /// on the same protection domain, with the given permission.
+    ///
+    /// The new memory region has its own local and remote keys. This is useful
+    /// when you want to expose only a narrow window of a larger buffer to a
+    /// remote peer, possibly with tighter permissions.
+    ///
+    /// Return an error if the range is out of bounds or empty.
+    pub fn reg_sub(&self, range: impl RangeBounds<usize>, perm: Permission) -> io::Result<Self> {
+        let slice = self
+            .slice_by_range(range)
+            .filter(|slice| slice.len() > 0)
+            .ok_or_else(|| {
+                IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "sub-range is empty or out of bounds",
+                )
+            })?;
+
+        // SAFETY: the range is within this MR, which is already registered.
+        unsafe { Self::reg(self.pd(), slice.addr(), slice.len(), perm) }
+    }
+
+    /// Bind a type 2 memory window to a sub-range of this memory region by
+    /// posting a bind request to the given QP, and return the remote handle
+    /// of the window.
+    ///
+    /// This is the recommended way to delegate remote access with reduced
+    /// permissions, e.g., when forwarding access to a memory region to a third
+    /// party. The remote key of a memory region carries all permissions given
+    /// at registration, while the returned handle only grants `perm` within
+    /// the given range. Binding the window again revokes the previous handle.
+    ///
+    /// The bind request is ordered before subsequent work requests posted to
+    /// the QP, so the handle can be sent to the QP's peer right away. Remote
+    /// peers should access the window through the QP connected to `qp`, as
+    /// some devices restrict type 2 windows to the QP they are bound with.
+    ///
+    /// **NOTE:** this memory region must be registered with
+    /// [`Permission::MW_BIND`], and `perm` must not contain local permissions.
+    ///
+    /// Return an error if the range is out of bounds or empty.
+    #[cfg(mlnx5)]
+    pub fn bind_window(
+        &self,
+        qp: &Qp,
+        mw: &Mw,
+        range: impl RangeBounds<usize>,
+        perm: Permission,
+        wr_id: WrId,
+        signal: bool,
+    ) -> io::Result<MrRemote> {
+        let slice = self
+            .slice_by_range(range)
+            .filter(|slice| slice.len() > 0)
+            .ok_or_else(|| {
+                IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "window range is empty or out of bounds",
+                )
+            })?;
+
+        // Providers do not pick remote keys for type 2 windows, so bump the
+        // tag byte of the current one as `ibv_bind_mw` would do.
+        let rkey = ibv_inc_rkey(mw.rkey());
+        let addr = slice.iova();
+        let len = slice.len();
+
+        let mut wr = ibv_send_wr {
+            wr_id,
+            next: ptr::null_mut(),
+            sg_list: ptr::null_mut(),
+            num_sge: 0,
+            opcode: ibv_wr_opcode::IBV_WR_BIND_MW,
+            send_flags: if signal {
+                ibv_send_flags::IBV_SEND_SIGNALED.0
+            } else {
+                0
+            },
+            bind_mw_tso: bind_mw_tso_union_t {
+                bind_mw: bind_mw_t {
+                    mw: mw.as_raw(),
+                    rkey,
+                    bind_info: ibv_mw_bind_info {
+                        mr: self.as_raw(),
+                        addr,
+                        length: len as u64,
+                        mw_access_flags: perm.into(),
+                    },
+                },
+            },
+            // SAFETY: POD type.
+            ..unsafe { mem::zeroed() }
+        };
+        let mut bad_wr = ptr::null_mut();
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_post_send(qp.as_raw(), &mut wr, &mut bad_wr) };
+        qp.send_posted(&wr, bad_wr);
+        from_c_ret(ret)?;
+
+        // SAFETY: the `ibv_mw` instance is valid, and the new key is effective
+        // for work requests posted after the bind.
+        unsafe { (*mw.as_raw()).rkey = rkey };
+        Ok(MrRemote::new(addr, len, rkey))
     }
 
     /// Get the underlying [`ibv_mr`] pointer.
@@ -127,6 +415,15 @@ impl Mr {
         self.mr.as_ptr()
     }
 
+    /// Get the underlying [`ibv_mr`] pointer as [`NonNull`].
+    ///
+    /// The pointer is valid only while this MR or any of its clones is
+    /// alive.
+    #[inline]
+    pub fn as_nonnull(&self) -> NonNull<ibv_mr> {
+        *self.mr
+    }
+
     /// Get the local key of the memory region.
     #[inline]
     pub fn lkey(&self) -> u32 {
@@ -139,6 +436,35 @@ impl Mr {
         self.mr.rkey()
     }
 
+    /// Return `true` if this memory region can serve as the target of RDMA
+    /// atomics as a whole, i.e., its base address is 8-byte aligned and its
+    /// length is a multiple of 8.
+    #[inline]
+    pub fn is_atomic_aligned(&self) -> bool {
+        self.addr() as usize % 8 == 0 && self.len() % 8 == 0
+    }
+
+    /// Get an iterator over the 8-byte cells of this memory region that can
+    /// serve as RDMA atomic operands, in address order.
+    ///
+    /// Every yielded slice is 8 bytes long and 8-byte aligned, and therefore
+    /// passes the checks of [`Qp::compare_swap()`] and [`Qp::fetch_add()`] by
+    /// construction. If the base address is not 8-byte aligned, the leading
+    /// bytes before the first aligned address are skipped; trailing bytes that
+    /// do not fill a whole cell are skipped as well. For a memory region
+    /// registered with [`Mr::reg_atomic()`], the cells cover it entirely.
+    ///
+    /// This makes it easy to use the memory region as an array of counters
+    /// accessible by remote peers, e.g., for distributed sequence numbers.
+    ///
+    /// [`Qp::compare_swap()`]: crate::rdma::qp::Qp::compare_swap
+    /// [`Qp::fetch_add()`]: crate::rdma::qp::Qp::fetch_add
+    pub fn as_atomic_cells(&self) -> impl Iterator<Item = MrSlice<'_>> {
+        let start = (self.addr() as usize).next_multiple_of(8) - self.addr() as usize;
+        let num = self.len().saturating_sub(start) / 8;
+        (0..num).map(move |i| MrSlice::new(self, start + i * 8, 8))
+    }
+
     /// Get the underlying [`Pd`].
     pub fn pd(&self) -> &Pd {
         &self.inner.pd
@@ -154,12 +480,92 @@ impl Mr {
         slice::from_raw_parts_mut(self.addr(), self.len())
     }
 
+    /// Get the NUMA node that the memory region resides on, inferred from its
+    /// backing pages, which registration has pinned in physical memory.
+    ///
+    /// Return `None` if the pages span multiple nodes, or if the node cannot
+    /// be determined, e.g., on kernels without NUMA support. Compare the
+    /// result with the NUMA node of the device to check whether the NIC
+    /// accesses the memory region without crossing the socket interconnect.
+    pub fn numa_node(&self) -> Option<u8> {
+        /// Number of pages queried with one system call.
+        const BATCH: usize = 512;
+
+        // SAFETY: FFI.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let start = self.addr() as usize / page_size * page_size;
+        let end = self.addr() as usize + self.len();
+
+        let mut node = None;
+        let mut pages = [std::ptr::null_mut::<libc::c_void>(); BATCH];
+        let mut status = [0 as libc::c_int; BATCH];
+        let mut addr = start;
+        while addr < end {
+            let n = ((end - addr).div_ceil(page_size)).min(BATCH);
+            for (i, page) in pages[..n].iter_mut().enumerate() {
+                *page = (addr + i * page_size) as _;
+            }
+            addr += n * page_size;
+
+            // SAFETY: FFI; without target nodes, `move_pages` moves nothing
+            // and only reports the nodes of the pages.
+            let ret = unsafe {
+                libc::syscall(
+                    libc::SYS_move_pages,
+                    0,
+                    n,
+                    pages.as_ptr(),
+                    std::ptr::null::<libc::c_int>(),
+                    status.as_mut_ptr(),
+                    0,
+                )
+            };
+            if ret != 0 {
+                return None;
+            }
+            for &page_node in &status[..n] {
+                if page_node < 0 || node.is_some_and(|node| node != page_node) {
+                    return None;
+                }
+                node = Some(page_node);
+            }
+        }
+        node.and_then(|node| u8::try_from(node).ok())
+    }
+
+    /// Return `true` if the memory region was registered with
+    /// [`Permission::ZERO_BASED`].
+    #[inline]
+    pub fn is_zero_based(&self) -> bool {
+        self.inner.zero_based
+    }
+
+    /// Get the address of the start of the memory region on the device,
+    /// i.e., `0` for zero-based memory regions, and the virtual address
+    /// otherwise.
+    #[inline]
+    pub fn iova(&self) -> u64 {
+        if self.inner.zero_based {
+            0
+        } else {
+            self.addr() as u64
+        }
+    }
+
     /// View this local memory region as a remote memory region for RDMA access
     /// from remote peers.
+    ///
+    /// The address of the returned handle is [`Mr::iova()`], i.e., `0` for
+    /// memory regions registered with [`Permission::ZERO_BASED`]. Remote
+    /// peers then address the memory region by offsets: arithmetic on the
+    /// handle, e.g., [`MrRemote::at()`] and slicing, works as usual, but the
+    /// address must not be compared with or converted from local pointers of
+    /// the peer, and handles of different zero-based memory regions overlap
+    /// in address and can only be told apart by their remote keys.
     #[inline]
     pub fn as_remote(&self) -> MrRemote {
         MrRemote {
-            addr: self.addr() as u64,
+            addr: self.iova(),
             len: self.len(),
             rkey: self.rkey(),
         }
@@ -28,6 +28,15 @@ impl<'a> MrSlice<'a> {
         self.mr
     }
 
+    /// Get the address of the slice on the device, which is used in
+    /// scatter-gather entries and remote handles. This differs from
+    /// [`Slicing::addr()`] for zero-based memory regions.
+    /// See [`Mr::iova()`] for details.
+    #[inline]
+    pub fn iova(&self) -> u64 {
+        self.mr.iova() + self.offset as u64
+    }
+
     /// Get the local key of the memory region.
     #[inline]
     pub fn lkey(&self) -> u32 {
@@ -86,7 +95,7 @@ where
 impl From<MrSlice<'_>> for ibv_sge {
     fn from(slice: MrSlice<'_>) -> Self {
         Self {
-            addr: slice.addr() as u64,
+            addr: slice.iova(),
             length: slice.len() as u32,
             lkey: slice.mr.lkey(),
         }
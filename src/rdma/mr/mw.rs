@@ -0,0 +1,142 @@
+use std::fmt;
+use std::io::{self, Error as IoError};
+use std::ptr::NonNull;
+use std::sync::Arc;
+#[cfg(mlnx5)]
+use std::{mem, ptr};
+
+use crate::bindings::*;
+use crate::rdma::pd::Pd;
+use crate::rdma::type_alias::RKey;
+#[cfg(mlnx5)]
+use crate::rdma::{qp::Qp, type_alias::WrId};
+use crate::utils::interop::from_c_ret;
+
+/// Wrapper for `*mut ibv_mw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub(crate) struct IbvMw(Option<NonNull<ibv_mw>>);
+
+impl IbvMw {
+    /// Get the current remote key of the memory window.
+    pub fn rkey(&self) -> u32 {
+        // SAFETY: the `ibv_mw` instance is valid.
+        unsafe { (*self.as_ptr()).rkey }
+    }
+
+    /// Deallocate the MW.
+    ///
+    /// # Safety
+    ///
+    /// - An MW must not be deallocated more than once.
+    /// - Deallocated MWs must not be used anymore.
+    pub unsafe fn dealloc(self) -> io::Result<()> {
+        // SAFETY: FFI.
+        let ret = ibv_dealloc_mw(self.as_ptr());
+        from_c_ret(ret)
+    }
+}
+
+impl_ibv_wrapper_traits!(ibv_mw, IbvMw);
+
+/// Ownership holder of memory window.
+struct MwInner {
+    pd: Pd,
+    mw: IbvMw,
+}
+
+impl Drop for MwInner {
+    fn drop(&mut self) {
+        // SAFETY: call only once, and no UAF since I will be dropped.
+        unsafe { self.mw.dealloc() }.expect("cannot dealloc MW on drop");
+    }
+}
+
+/// Type 2 memory window.
+///
+/// A memory window grants remote access to a part of a memory region with its
+/// own remote key and permissions, which can be narrower than those of the
+/// memory region. It is bound to a memory region with
+/// [`Mr::bind_window()`](super::Mr::bind_window), and can be re-bound any
+/// time, which invalidates the remote key of the previous binding.
+#[derive(Clone)]
+pub struct Mw {
+    /// Cached memory window pointer.
+    mw: IbvMw,
+
+    /// Memory window body.
+    inner: Arc<MwInner>,
+}
+
+impl fmt::Debug for Mw {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("Mw<{:p}>", self.as_raw()))
+    }
+}
+
+impl Mw {
+    /// Allocate a type 2 memory window on the given protection domain.
+    pub fn new(pd: &Pd) -> io::Result<Self> {
+        let _guard = pd.context().lock_creation();
+        // SAFETY: FFI.
+        let mw = unsafe { ibv_alloc_mw(pd.as_raw(), ibv_mw_type::IBV_MW_TYPE_2) };
+        let mw = NonNull::new(mw).ok_or_else(IoError::last_os_error)?;
+        let mw = IbvMw::from(mw);
+
+        Ok(Self {
+            inner: Arc::new(MwInner { pd: pd.clone(), mw }),
+            mw,
+        })
+    }
+
+    /// Get the underlying [`ibv_mw`] pointer.
+    #[inline]
+    pub fn as_raw(&self) -> *mut ibv_mw {
+        self.mw.as_ptr()
+    }
+
+    /// Get the remote key of the latest binding of the memory window.
+    #[inline]
+    pub fn rkey(&self) -> RKey {
+        self.mw.rkey()
+    }
+
+    /// Get the underlying [`Pd`].
+    pub fn pd(&self) -> &Pd {
+        &self.inner.pd
+    }
+
+    /// Invalidate the latest binding of the memory window by posting a local
+    /// invalidate request to the given QP, which should be the QP that the
+    /// window was bound with.
+    ///
+    /// The request is ordered before subsequent work requests posted to the
+    /// QP, but remote accesses already in flight may still complete; poll
+    /// its completion to be sure that the remote key is revoked. The window
+    /// can be bound again afterwards.
+    #[cfg(mlnx5)]
+    pub fn invalidate(&self, qp: &Qp, wr_id: WrId, signal: bool) -> io::Result<()> {
+        let mut wr = ibv_send_wr {
+            wr_id,
+            next: ptr::null_mut(),
+            sg_list: ptr::null_mut(),
+            num_sge: 0,
+            opcode: ibv_wr_opcode::IBV_WR_LOCAL_INV,
+            send_flags: if signal {
+                ibv_send_flags::IBV_SEND_SIGNALED.0
+            } else {
+                0
+            },
+            imm_data_invalidated_rkey_union: imm_data_invalidated_rkey_union_t {
+                invalidated_rkey: self.rkey(),
+            },
+            // SAFETY: POD type.
+            ..unsafe { mem::zeroed() }
+        };
+        let mut bad_wr = ptr::null_mut();
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_post_send(qp.as_raw(), &mut wr, &mut bad_wr) };
+        qp.send_posted(&wr, bad_wr);
+        from_c_ret(ret)
+    }
+}
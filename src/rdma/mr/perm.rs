@@ -3,6 +3,7 @@ use std::ops::{Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, Su
 use crate::bindings::ibv_access_flags;
 
 /// Memory region permissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct Permission(ibv_access_flags);
 
@@ -13,10 +14,23 @@ impl Permission {
     pub const REMOTE_WRITE: Self = Self(ibv_access_flags::IBV_ACCESS_REMOTE_WRITE);
     pub const REMOTE_ATOMIC: Self = Self(ibv_access_flags::IBV_ACCESS_REMOTE_ATOMIC);
     pub const MW_BIND: Self = Self(ibv_access_flags::IBV_ACCESS_MW_BIND);
+
+    /// Register the memory region with device addresses starting from zero
+    /// instead of its virtual address, so that remote peers address it by
+    /// offsets and do not learn the local address space layout.
+    /// See [`Mr::as_remote()`](super::Mr::as_remote) for details.
     pub const ZERO_BASED: Self = Self(ibv_access_flags::IBV_ACCESS_ZERO_BASED);
     pub const ON_DEMAND: Self = Self(ibv_access_flags::IBV_ACCESS_ON_DEMAND);
 }
 
+impl Permission {
+    /// Return `true` if all permissions in `other` are also in `self`.
+    #[inline]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 .0 & other.0 .0 == other.0 .0
+    }
+}
+
 impl Default for Permission {
     /// Allow local write, remote read/write, and remote atomic.
     fn default() -> Self {
@@ -1,3 +1,8 @@
+use std::fmt;
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
 use super::{MrSlice, Slicing};
 use crate::bindings::*;
 
@@ -8,13 +13,30 @@ use crate::bindings::*;
 /// have a `RemoteMemSlice` counterpart, as this type itself can represent a
 /// remote memory region slice by letting `addr` and `len` correspond to only
 /// a part of the entire remote memory region.
-#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+///
+/// `addr` is the address on the remote device, which is an offset rather than
+/// a virtual address if the remote memory region is zero-based.
+/// See [`Mr::as_remote()`](super::Mr::as_remote) for details.
+///
+/// With the `zeroize` feature, this type implements `Zeroize`. Being `Copy`,
+/// it cannot be zeroized on drop; call `zeroize()` on every copy once the
+/// remote access is no longer needed.
+#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct MrRemote {
     pub addr: u64,
     pub len: usize,
     pub rkey: u32,
 }
 
+#[cfg(feature = "zeroize")]
+impl Zeroize for MrRemote {
+    fn zeroize(&mut self) {
+        self.addr.zeroize();
+        self.len.zeroize();
+        self.rkey.zeroize();
+    }
+}
+
 impl MrRemote {
     /// Create a new piece of remote registered memory data.
     pub fn new(addr: u64, len: usize, rkey: u32) -> Self {
@@ -32,6 +54,12 @@ impl MrRemote {
         self.addr + offset as u64
     }
 
+    /// Get the end address (exclusive) of this piece of remote memory.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.addr + self.len as u64
+    }
+
     /// Generate a [`rdma_t`] instance for RDMA one-sided operations to this
     /// piece of remote memory.
     #[inline]
@@ -50,6 +78,32 @@ impl Default for MrRemote {
     }
 }
 
+impl fmt::Debug for MrRemote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MrRemote")
+            .field("addr", &format_args!("{:#x}", self.addr))
+            .field("len", &self.len)
+            .field("rkey", &format_args!("{:#x}", self.rkey))
+            .field("end", &format_args!("{:#x}", self.end()))
+            .finish()
+    }
+}
+
+impl fmt::Display for MrRemote {
+    /// Show the remote memory as its address range, e.g.,
+    /// `[0x7f0000001000, 0x7f0000002000) len=4096 rkey=0x1a2b`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:#x}, {:#x}) len={} rkey={:#x}",
+            self.addr,
+            self.end(),
+            self.len,
+            self.rkey
+        )
+    }
+}
+
 unsafe impl<'s> Slicing<'s> for MrRemote {
     type Output = Self;
 
@@ -75,7 +129,7 @@ unsafe impl<'s> Slicing<'s> for MrRemote {
 impl From<MrSlice<'_>> for MrRemote {
     fn from(slice: MrSlice<'_>) -> Self {
         Self {
-            addr: slice.addr() as u64,
+            addr: slice.iova(),
             len: slice.len(),
             rkey: slice.mr().rkey(),
         }
@@ -8,6 +8,7 @@ use std::slice;
 
 use crate::bindings::*;
 use crate::rdma::context::IbvContext;
+use crate::rdma::fork;
 
 /// Wrapper for `*mut ibv_device`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -31,7 +32,16 @@ impl IbvDevice {
             .to_owned())
     }
 
+    /// Get the node GUID of this device, in host byte order.
+    pub fn guid(&self) -> u64 {
+        // SAFETY: FFI.
+        let guid = unsafe { ibv_get_device_guid(self.as_ptr()) };
+        u64::from_be(guid)
+    }
+
     /// Get the NUMA node of this device.
+    /// Fail with `InvalidData` if the kernel does not know the node, e.g., on
+    /// systems without NUMA support, where sysfs reports `-1`.
     pub fn numa_node(&self) -> io::Result<u8> {
         let name = self.name()?;
 
@@ -42,14 +52,18 @@ impl IbvDevice {
         let mut buf = String::new();
         File::open(path)?.read_to_string(&mut buf)?;
 
-        Ok(buf
-            .trim()
-            .parse::<u8>()
-            .expect("invalid NUMA node information in sysfs"))
+        buf.trim().parse::<u8>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown NUMA node {:?} in sysfs", buf.trim()),
+            )
+        })
     }
 
     /// Open the device to get a context.
     pub fn open(self) -> io::Result<IbvContext> {
+        fork::auto_fork_init();
+
         // SAFETY: FFI.
         let ctx = unsafe { ibv_open_device(self.as_ptr()) };
         let ctx = NonNull::new(ctx).ok_or_else(io::Error::last_os_error)?;
@@ -61,7 +75,7 @@ impl_ibv_wrapper_traits!(RAW, ibv_device, IbvDevice);
 
 /// Wrapper for `*mut *mut ibv_device`.
 #[repr(transparent)]
-pub(super) struct IbvDeviceList(ManuallyDrop<Box<[IbvDevice]>>);
+pub(crate) struct IbvDeviceList(ManuallyDrop<Box<[IbvDevice]>>);
 
 impl IbvDeviceList {
     /// Get a list of RDMA physical devices.
@@ -4,6 +4,7 @@ mod device;
 mod port;
 
 use std::io::Error as IoError;
+use std::net::Ipv4Addr;
 
 use regex::Regex;
 use thiserror::Error;
@@ -11,6 +12,7 @@ use thiserror::Error;
 pub(crate) use self::device::*;
 pub use self::port::*;
 use super::context::*;
+use super::type_alias::{GidIndex, PortNum};
 
 /// Port speed filter type.
 enum PortSpeedFilter {
@@ -263,8 +265,10 @@ impl NicFinder {
                         // Eligible port
                         if n == 0 {
                             let port = Port::new(ctx, port_num)?;
+                            let mut context = Context::new(ctx, attr);
+                            context.set_ports(vec![port_num]);
                             return Ok(Nic {
-                                context: Context::new(ctx, attr),
+                                context,
                                 ports: vec![port],
                             });
                         } else {
@@ -321,4 +325,14 @@ impl Nic {
     pub fn finder() -> NicFinder {
         Default::default()
     }
+
+    /// Find the index of the RoCEv2 GID that embeds the given IPv4 address on
+    /// the port of the given number. Return `None` if the port is not probed
+    /// or has no such GID. See [`Port::gid_index_for_ip()`].
+    pub fn gid_index_for_ip(&self, port_num: PortNum, ip: Ipv4Addr) -> Option<GidIndex> {
+        self.ports
+            .iter()
+            .find(|port| port.num() == port_num)?
+            .gid_index_for_ip(ip)
+    }
 }
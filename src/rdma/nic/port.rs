@@ -1,11 +1,14 @@
 use std::fmt::Display;
-use std::{hint, io, mem};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::{fs, hint, io, mem};
 
 use thiserror::Error;
 
 use crate::bindings::*;
 use crate::rdma::context::IbvContext;
 use crate::rdma::gid::*;
+use crate::rdma::type_alias::GidIndex;
 
 /// Physical port information.
 #[derive(Clone)]
@@ -18,6 +21,9 @@ pub struct Port {
 
     /// GIDs of this port.
     gids: Vec<GidTyped>,
+
+    /// Network devices associated with the GIDs, indexed by GID index.
+    netdevs: Vec<Option<String>>,
 }
 
 unsafe impl Send for Port {}
@@ -61,7 +67,32 @@ impl Port {
             }
         }
 
-        Ok(Self { num, attr, gids })
+        // Network devices are only known to sysfs, and only RoCE GIDs have them.
+        let dev_name = ctx.dev().name()?;
+        let netdevs = (0..gids.len())
+            .map(|i| Self::query_gid_netdev(&dev_name, num, i))
+            .collect();
+
+        Ok(Self {
+            num,
+            attr,
+            gids,
+            netdevs,
+        })
+    }
+
+    /// Read the name of the network device associated with the given GID from
+    /// sysfs. Return `None` if there is none.
+    fn query_gid_netdev(dev_name: &str, num: u8, gid_index: usize) -> Option<String> {
+        let path = Path::new("/sys/class/infiniband")
+            .join(dev_name)
+            .join("ports")
+            .join(num.to_string())
+            .join("gid_attrs/ndevs")
+            .join(gid_index.to_string());
+        let netdev = fs::read_to_string(path).ok()?;
+        let netdev = netdev.trim();
+        (!netdev.is_empty()).then(|| netdev.to_owned())
     }
 
     /// Get the index of this port.
@@ -111,21 +142,41 @@ impl Port {
     }
 
     /// Get the active MTU of this port.
+    ///
+    /// This is an alias of [`Port::active_mtu()`].
     #[inline]
     pub fn mtu(&self) -> PortMtu {
-        match self.attr.active_mtu {
-            ibv_mtu::IBV_MTU_256 => PortMtu::Mtu256,
-            ibv_mtu::IBV_MTU_512 => PortMtu::Mtu512,
-            ibv_mtu::IBV_MTU_1024 => PortMtu::Mtu1024,
-            ibv_mtu::IBV_MTU_2048 => PortMtu::Mtu2048,
-            ibv_mtu::IBV_MTU_4096 => PortMtu::Mtu4096,
+        self.active_mtu()
+    }
 
-            // SAFETY: enum constraints of `libibverbs`.
-            _ => unsafe { hint::unreachable_unchecked() },
-        }
+    /// Get the active MTU of this port, which is the MTU currently in effect
+    /// on the link. It can be smaller than [`Port::max_mtu()`].
+    #[inline]
+    pub fn active_mtu(&self) -> PortMtu {
+        PortMtu::from_raw(self.attr.active_mtu)
+    }
+
+    /// Get the maximum message length supported by this port, in bytes.
+    #[inline]
+    pub fn max_msg_len(&self) -> usize {
+        self.attr.max_msg_sz as usize
+    }
+
+    /// Get the maximum MTU supported by this port.
+    #[inline]
+    pub fn max_mtu(&self) -> PortMtu {
+        PortMtu::from_raw(self.attr.max_mtu)
     }
 
-    /// Get the active speed of this port in Gbps.
+    /// Get the active speed of this port in Gbps, i.e., the per-lane rate
+    /// times the number of lanes.
+    ///
+    /// Rates up to NDR (100Gbps per lane, 400Gbps on a 4x port) are decoded
+    /// from `active_speed`. XDR (200Gbps per lane) does not fit in this 8-bit
+    /// field, and kernels that support it report zero there, with the actual
+    /// value in `active_speed_ex`; a zero `active_speed` of an active port is
+    /// therefore decoded as XDR. Unknown widths or rates decode to a speed of
+    /// zero.
     #[inline]
     pub fn speed(&self) -> PortSpeed {
         let width: u32 = match self.attr.active_width {
@@ -133,9 +184,8 @@ impl Port {
             2 => 4,
             4 => 8,
             8 => 12,
-
-            // SAFETY: enum constraints of `libibverbs`.
-            _ => unsafe { hint::unreachable_unchecked() },
+            16 => 2,
+            _ => 0,
         };
 
         // Speed times 10 to avoid floating point numbers, which are `!Eq`.
@@ -146,9 +196,9 @@ impl Port {
             16 => 140,
             32 => 250,
             64 => 500,
-
-            // SAFETY: enum constraints of `libibverbs`.
-            _ => unsafe { hint::unreachable_unchecked() },
+            128 => 1000,
+            0 if self.state() == PortState::Active => 2000,
+            _ => 0,
         };
         PortSpeed(width * speed10x)
     }
@@ -158,6 +208,41 @@ impl Port {
         &self.gids
     }
 
+    /// Get the GID table of this port, with the type, index and associated
+    /// network device of each GID.
+    ///
+    /// Use this method to select a GID by yourself, e.g., the RoCEv2 GID of a
+    /// specific network interface. Pass the index to
+    /// [`Qp::bind_local_port()`](crate::rdma::qp::Qp::bind_local_port).
+    pub fn gids_typed(&self) -> Vec<GidEntry> {
+        self.gids
+            .iter()
+            .zip(&self.netdevs)
+            .enumerate()
+            .map(|(index, (gid, netdev))| GidEntry {
+                gid: gid.gid,
+                gid_type: gid.ty,
+                netdev: netdev.clone(),
+                index: index as _,
+            })
+            .collect()
+    }
+
+    /// Find the index of the RoCEv2 GID that embeds the given IPv4 address,
+    /// i.e., the GID of the network interface with that address. Return
+    /// `None` if there is no such GID.
+    ///
+    /// Use this method to bind QPs to the same interface whose IP address is
+    /// used for out-of-band communication, e.g., by [`Cluster`](crate::ctrl::Cluster).
+    pub fn gid_index_for_ip(&self, ip: Ipv4Addr) -> Option<GidIndex> {
+        self.gids
+            .iter()
+            .position(|gid| {
+                gid.ty == GidType::RoceV2 && Ipv6Addr::from(gid.gid).to_ipv4_mapped() == Some(ip)
+            })
+            .map(|index| index as _)
+    }
+
     /// Get the most recommended GID of this port.
     /// Using this GID should generally work well.
     /// - Infiniband is preferred over RoCEv2, then RoCEv1.
@@ -248,6 +333,18 @@ pub enum PortState {
     ActiveDefer = ibv_port_state::IBV_PORT_ACTIVE_DEFER as _,
 }
 
+/// Port state change, reported by the asynchronous events of a device.
+/// See [`Context::port_events()`](crate::rdma::context::Context::port_events).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortEvent {
+    /// The port with the contained index became active.
+    Active(u8),
+
+    /// The port with the contained index went down, so QPs bound to it can no
+    /// longer communicate.
+    Down(u8),
+}
+
 /// Port link layer protocol type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PortLinkLayer {
@@ -259,7 +356,9 @@ pub enum PortLinkLayer {
 }
 
 /// Port MTU size.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
 pub enum PortMtu {
     /// 256 bytes.
     Mtu256 = ibv_mtu::IBV_MTU_256 as _,
@@ -278,6 +377,21 @@ pub enum PortMtu {
 }
 
 impl PortMtu {
+    /// Cast `ibv_mtu` into `PortMtu`.
+    #[inline]
+    fn from_raw(mtu: ibv_mtu::Type) -> Self {
+        match mtu {
+            ibv_mtu::IBV_MTU_256 => Self::Mtu256,
+            ibv_mtu::IBV_MTU_512 => Self::Mtu512,
+            ibv_mtu::IBV_MTU_1024 => Self::Mtu1024,
+            ibv_mtu::IBV_MTU_2048 => Self::Mtu2048,
+            ibv_mtu::IBV_MTU_4096 => Self::Mtu4096,
+
+            // SAFETY: enum constraints of `libibverbs`.
+            _ => unsafe { hint::unreachable_unchecked() },
+        }
+    }
+
     /// Get the MTU size in bytes.
     #[inline]
     pub fn bytes(&self) -> usize {
@@ -304,7 +418,8 @@ impl Display for PortMtu {
 pub struct PortSpeed(pub(crate) u32);
 
 impl PortSpeed {
-    pub const MAX_GBPS: f32 = 600.0;
+    /// The highest possible speed, i.e., a 12x XDR port.
+    pub const MAX_GBPS: f32 = 2400.0;
 
     /// Get the speed in Gbps.
     #[inline]
@@ -2,9 +2,10 @@
 
 use std::io::{self, Error as IoError};
 use std::ptr::NonNull;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 
 use super::context::Context;
+use super::mr::{Mr, MrInner, Permission};
 use crate::bindings::*;
 use crate::utils::interop::from_c_ret;
 
@@ -33,6 +34,9 @@ impl_ibv_wrapper_traits!(ibv_pd, IbvPd);
 struct PdInner {
     ctx: Context,
     pd: IbvPd,
+
+    /// Memory regions registered in this PD, for [`Pd::dereg_all()`].
+    mrs: Mutex<Vec<Weak<MrInner>>>,
 }
 
 impl Drop for PdInner {
@@ -50,11 +54,15 @@ pub struct Pd {
 
     /// Protection domain body.
     inner: Arc<PdInner>,
+
+    /// Permission of memory regions registered without an explicit one.
+    default_perm: Permission,
 }
 
 impl Pd {
     /// Allocate a protection domain for the given RDMA device context.
     pub fn new(ctx: &Context) -> io::Result<Self> {
+        let _guard = ctx.lock_creation();
         // SAFETY: FFI
         let pd = unsafe { ibv_alloc_pd(ctx.as_raw()) };
         let pd = NonNull::new(pd).ok_or_else(IoError::last_os_error)?;
@@ -64,8 +72,10 @@ impl Pd {
             inner: Arc::new(PdInner {
                 ctx: ctx.clone(),
                 pd,
+                mrs: Mutex::new(Vec::new()),
             }),
             pd,
+            default_perm: Permission::default(),
         })
     }
 
@@ -81,6 +91,70 @@ impl Pd {
         &self.inner.ctx
     }
 
+    /// Set the permission of memory regions registered through this handle
+    /// without an explicit one, e.g., with [`Pd::reg()`] and
+    /// [`RegisteredMem`](crate::wrap::RegisteredMem). Clones of the returned
+    /// handle inherit the policy; other handles of the same PD are unaffected.
+    ///
+    /// This centralizes the security posture of a tenant, e.g., disallowing
+    /// remote atomics on all of its buffers:
+    ///
+    /// ```ignore
+    /// let pd = Pd::new(&ctx)?.with_default_permission(Permission::default() - Permission::REMOTE_ATOMIC);
+    /// ```
+    ///
+    /// If not set, the default permission is [`Permission::default()`].
+    pub fn with_default_permission(mut self, perm: Permission) -> Self {
+        self.default_perm = perm;
+        self
+    }
+
+    /// Get the permission of memory regions registered without an explicit one.
+    #[inline]
+    pub fn default_permission(&self) -> Permission {
+        self.default_perm
+    }
+
+    /// Register a memory region on the given range of virtual memory, with the
+    /// default permission of this handle.
+    ///
+    /// # Safety
+    ///
+    /// See [`Mr::reg()`].
+    pub unsafe fn reg(&self, buf: *mut u8, len: usize) -> io::Result<Mr> {
+        Mr::reg(self, buf, len, self.default_perm)
+    }
+
+    /// Record a memory region registered in this PD.
+    pub(crate) fn track_mr(&self, mr: &Arc<MrInner>) {
+        let mut mrs = self.inner.mrs.lock().unwrap();
+        mrs.retain(|tracked| tracked.strong_count() > 0);
+        mrs.push(Arc::downgrade(mr));
+    }
+
+    /// Deregister all memory regions in this PD that are still alive, in the
+    /// order they were registered. Return the errors of failed
+    /// deregistrations, if any.
+    ///
+    /// Dropping an [`Mr`](crate::prelude::Mr) never panics: a failed
+    /// deregistration is only logged, and the MR is leaked. Call this method
+    /// before tearing down a PD to deregister MRs in a well-defined order and
+    /// observe failures. MRs deregistered here are skipped when dropped.
+    ///
+    /// # Safety
+    ///
+    /// No memory region of this PD may be used after this call, except being
+    /// dropped. This includes [`MrSlice`](crate::prelude::MrSlice)s, and
+    /// work requests posted or to be posted with their local keys.
+    pub unsafe fn dereg_all(&self) -> Vec<io::Error> {
+        let mrs = std::mem::take(&mut *self.inner.mrs.lock().unwrap());
+        mrs.iter()
+            .filter_map(Weak::upgrade)
+            // SAFETY: guaranteed by the caller.
+            .filter_map(|mr| unsafe { mr.dereg_once() }.err())
+            .collect()
+    }
+
     /// Consume and leak the `Pd`, returning the underlying `ibv_pd` pointer.
     /// The method receiver must be the only instance of the same protection domain, i.e.,
     ///
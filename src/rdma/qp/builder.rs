@@ -1,19 +1,31 @@
 #[cfg(mlnx4)]
 use std::collections::HashSet;
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
 use std::mem;
 
 use crate::bindings::*;
 use crate::rdma::cq::*;
 use crate::rdma::pd::*;
+use crate::rdma::type_alias::Psn;
 
+#[cfg(mlnx5)]
+use super::QpEx;
 use super::{Qp, QpCreationError, QpType};
 
 /// Experimental features available in MLNX_OFED v4.x drivers.
-#[cfg(mlnx4)]
+///
+/// The type is defined on all backends, so that applications can probe for
+/// the features with [`Context::supports()`](crate::rdma::context::Context::supports),
+/// but they are only available with MLNX_OFED v4.x.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ExpFeature {
     /// Enable extended atomic compare-and-swap & fetch-and-add.
     ExtendedAtomics,
+
+    /// Dynamically connected transport, i.e., DCTs and DC initiator QPs.
+    /// It is selected by the QP type, so enabling it on a QP has no effect.
+    DynamicConnected,
 }
 
 /// Queue pair capability attributes.
@@ -62,6 +74,10 @@ pub struct QpCaps {
 
     /// The maximum message size (in bytes) that can be posted inline to the
     /// Send Queue. If no inline message is requested, the value can be 0.
+    ///
+    /// **NOTE:** If the device rejects the requested value, it is halved and
+    /// then set to 0 until the QP can be created. A warning is logged on
+    /// every downgrade.
     pub max_inline_data: u32,
 }
 
@@ -85,6 +101,18 @@ impl Default for QpCaps {
     }
 }
 
+impl From<ibv_qp_cap> for QpCaps {
+    fn from(cap: ibv_qp_cap) -> Self {
+        QpCaps {
+            max_send_wr: cap.max_send_wr,
+            max_recv_wr: cap.max_recv_wr,
+            max_send_sge: cap.max_send_sge,
+            max_recv_sge: cap.max_recv_sge,
+            max_inline_data: cap.max_inline_data,
+        }
+    }
+}
+
 impl QpCaps {
     /// Generate a default RDMA queue pair capabilities setting for DC initiator.
     /// The queue pair capabilities are set to:
@@ -107,6 +135,21 @@ impl QpCaps {
             max_inline_data: 64,
         }
     }
+
+    /// Check that an inline post of the given total length fits in
+    /// `max_inline_data`.
+    pub(crate) fn check_inline(&self, len: usize) -> io::Result<()> {
+        if len > self.max_inline_data as usize {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                format!(
+                    "{} bytes exceed the inline capacity {} of this QP",
+                    len, self.max_inline_data
+                ),
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Queue pair builder.
@@ -130,9 +173,20 @@ pub struct QpBuilder<'a> {
     /// Whether to use global routing. Default is `true`.
     pub(super) global_routing: bool,
 
+    /// Initial packet sequence number of the send queue.
+    pub(super) init_psn: Psn,
+
+    /// Whether to track the occupancy of the send queue. Default is `false`.
+    pub(super) track_sq: bool,
+
     /// Enabled experimental features.
     #[cfg(mlnx4)]
     pub(super) features: HashSet<ExpFeature>,
+
+    /// Send operations enabled for the extended work request API, as
+    /// `ibv_qp_create_send_ops_flags`. Zero means the legacy API only.
+    #[cfg(mlnx5)]
+    pub(super) send_ops_flags: u64,
 }
 
 impl<'a> QpBuilder<'a> {
@@ -146,9 +200,14 @@ impl<'a> QpBuilder<'a> {
             qp_type: None,
             sq_sig_all: None,
             global_routing: true,
+            init_psn: Qp::GLOBAL_INIT_PSN,
+            track_sq: false,
 
             #[cfg(mlnx4)]
             features: Default::default(),
+
+            #[cfg(mlnx5)]
+            send_ops_flags: 0,
         }
     }
 
@@ -178,6 +237,8 @@ impl<'a> QpBuilder<'a> {
     }
 
     /// Set whether to signal for all send work requests.
+    /// If set, every send work request generates a completion, regardless of
+    /// the `signal` argument passed when posting it.
     pub fn sq_sig_all(mut self, sq_sig_all: bool) -> Self {
         self.sq_sig_all = Some(sq_sig_all);
         self
@@ -195,6 +256,34 @@ impl<'a> QpBuilder<'a> {
         self
     }
 
+    /// Set the initial packet sequence number (PSN) of the send queue.
+    /// If not set, [`Qp::GLOBAL_INIT_PSN`] is used.
+    ///
+    /// The PSN is carried in the QP's [`QpEndpoint`](super::QpEndpoint), and
+    /// the remote peer uses it as the expected PSN of its receive queue when
+    /// connecting. Choosing distinct (e.g., random) PSNs for different
+    /// connections helps the device reject stale packets of earlier
+    /// connections between the same QP numbers.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `psn` does not fit in 24 bits.
+    pub fn init_psn(mut self, psn: Psn) -> Self {
+        assert!(psn < (1 << 24), "PSN {:#x} does not fit in 24 bits", psn);
+        self.init_psn = psn;
+        self
+    }
+
+    /// Set whether to track the occupancy of the send queue, as reported by
+    /// [`Qp::sq_depth_in_use()`]. If not set, tracking is disabled.
+    ///
+    /// Tracking takes a lock on every post and every accounted completion,
+    /// which costs throughput on QPs posted to from many threads.
+    pub fn track_sq(mut self, track_sq: bool) -> Self {
+        self.track_sq = track_sq;
+        self
+    }
+
     /// Enable experimental features for the QP.
     #[cfg(mlnx4)]
     pub fn enable_feature(mut self, feature: ExpFeature) -> Self {
@@ -211,12 +300,37 @@ impl<'a> QpBuilder<'a> {
 
     /// Build the queue pair on the given protection domain.
     ///
+    /// Fail with [`QpCreationError::InvalidConfiguration`] if the send or
+    /// receive CQ belongs to a different device context than the PD.
+    ///
     /// # Panics
     ///
     /// Panic if any mandatory field (except QP capabilities) is not set.
     pub fn build(self, pd: &Pd) -> Result<Qp, QpCreationError> {
         Qp::new(pd, self)
     }
+
+    /// Build the queue pair on the given protection domain, and return a
+    /// report of the adjustments made by the crate and the driver during
+    /// creation, e.g., for troubleshooting unexpected capabilities.
+    ///
+    /// # Panics
+    ///
+    /// Panic if any mandatory field (except QP capabilities) is not set.
+    pub fn build_with_report(self, pd: &Pd) -> Result<(Qp, QpBuildReport), QpCreationError> {
+        Qp::new_with_report(pd, self)
+    }
+
+    /// Build a queue pair that supports the extended work request API on the
+    /// given protection domain. See [`QpEx`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panic if any mandatory field (except QP capabilities) is not set.
+    #[cfg(mlnx5)]
+    pub fn build_ex(self, pd: &Pd) -> Result<QpEx, QpCreationError> {
+        QpEx::new(pd, self)
+    }
 }
 
 impl<'a> QpBuilder<'a> {
@@ -230,9 +344,14 @@ impl<'a> QpBuilder<'a> {
             qp_type: self.qp_type.expect("QP type must be set"),
             sq_sig_all: self.sq_sig_all.expect("sq_sig_all must be explicitly set"),
             global_routing: self.global_routing,
+            init_psn: self.init_psn,
+            track_sq: self.track_sq,
 
             #[cfg(mlnx4)]
             features: self.features,
+
+            #[cfg(mlnx5)]
+            send_ops_flags: self.send_ops_flags,
         }
     }
 }
@@ -243,6 +362,50 @@ impl Default for QpBuilder<'_> {
     }
 }
 
+/// Diagnostic report of queue pair creation, returned by
+/// [`QpBuilder::build_with_report()`].
+#[derive(Clone, Copy, Debug)]
+pub struct QpBuildReport {
+    /// Type of the created QP.
+    pub qp_type: QpType,
+
+    /// Capabilities requested in the builder.
+    pub requested: QpCaps,
+
+    /// Capabilities granted by the device, as queried after creation.
+    pub granted: QpCaps,
+
+    /// The inline size the QP was finally created with, if the device
+    /// rejected the requested one and creation was retried with less.
+    pub inline_downgraded_to: Option<u32>,
+}
+
+impl QpBuildReport {
+    /// Get the capabilities that were granted differently than requested, as
+    /// `(name, requested, granted)` tuples. Devices commonly round queue
+    /// depths and inline sizes up, and may grant less than requested when
+    /// creation has been retried.
+    pub fn adjusted_caps(&self) -> Vec<(&'static str, u32, u32)> {
+        let (req, got) = (&self.requested, &self.granted);
+        [
+            ("max_send_wr", req.max_send_wr, got.max_send_wr),
+            ("max_recv_wr", req.max_recv_wr, got.max_recv_wr),
+            ("max_send_sge", req.max_send_sge, got.max_send_sge),
+            ("max_recv_sge", req.max_recv_sge, got.max_recv_sge),
+            ("max_inline_data", req.max_inline_data, got.max_inline_data),
+        ]
+        .into_iter()
+        .filter(|(_, req, got)| req != got)
+        .collect()
+    }
+
+    /// Return `true` if any capability was granted differently than
+    /// requested, or the inline size was downgraded.
+    pub fn has_adjustments(&self) -> bool {
+        self.inline_downgraded_to.is_some() || !self.adjusted_caps().is_empty()
+    }
+}
+
 /// Initialization attributes of a queue pair.
 pub(super) struct QpInitAttr {
     /// Send completion queue for this QP.
@@ -263,9 +426,19 @@ pub(super) struct QpInitAttr {
     /// Whether to use global routing.
     pub global_routing: bool,
 
+    /// Initial packet sequence number of the send queue.
+    pub init_psn: Psn,
+
+    /// Whether to track the occupancy of the send queue.
+    pub track_sq: bool,
+
     /// Experimental feature flags.
     #[cfg(mlnx4)]
     pub features: HashSet<ExpFeature>,
+
+    /// Send operations enabled for the extended work request API.
+    #[cfg(mlnx5)]
+    pub send_ops_flags: u64,
 }
 
 impl QpInitAttr {
@@ -325,6 +498,7 @@ impl QpInitAttr {
                         ibv_exp_qp_init_attr_comp_mask::IBV_EXP_QP_INIT_ATTR_ATOMICS_ARG.0;
                     attr.max_atomic_arg = 1 << dev_attr.ext_atom.log_max_atomic_inline;
                 }
+                ExpFeature::DynamicConnected => {}
             }
         }
         attr
@@ -333,7 +507,7 @@ impl QpInitAttr {
     /// Create an [`ibv_qp_init_attr_ex`] from the attributes.
     #[allow(unused)]
     pub fn to_init_attr_ex(&self, pd: &Pd) -> ibv_qp_init_attr_ex {
-        ibv_qp_init_attr_ex {
+        let mut attr = ibv_qp_init_attr_ex {
             send_cq: self.send_cq.as_raw(),
             recv_cq: self.recv_cq.as_raw(),
             cap: ibv_qp_cap {
@@ -348,6 +522,54 @@ impl QpInitAttr {
             pd: pd.as_raw(),
             comp_mask: ibv_qp_init_attr_mask::IBV_QP_INIT_ATTR_PD.0,
             ..unsafe { mem::zeroed() }
+        };
+
+        #[cfg(mlnx5)]
+        if self.send_ops_flags != 0 {
+            attr.comp_mask |= ibv_qp_init_attr_mask::IBV_QP_INIT_ATTR_SEND_OPS_FLAGS.0;
+            attr.send_ops_flags = self.send_ops_flags;
         }
+        attr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Capabilities as a device that grants only 64 bytes of inline data
+    /// reports them after creation.
+    fn granted_cap() -> ibv_qp_cap {
+        // SAFETY: POD type.
+        let mut cap = unsafe { mem::zeroed::<ibv_qp_cap>() };
+        cap.max_send_wr = 128;
+        cap.max_recv_wr = 128;
+        cap.max_send_sge = 16;
+        cap.max_recv_sge = 16;
+        cap.max_inline_data = 64;
+        cap
+    }
+
+    #[test]
+    fn cached_inline_capacity_reflects_granted_limit() {
+        let requested = QpCaps {
+            max_inline_data: 256,
+            ..QpCaps::default()
+        };
+        let granted = QpCaps::from(granted_cap());
+        assert_eq!(granted.max_inline_data, 64);
+
+        assert!(granted.check_inline(64).is_ok());
+        let err = granted.check_inline(256).unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidInput);
+
+        let report = QpBuildReport {
+            qp_type: QpType::Rc,
+            requested,
+            granted,
+            inline_downgraded_to: None,
+        };
+        assert_eq!(report.adjusted_caps(), vec![("max_inline_data", 256, 64)]);
+        assert!(report.has_adjustments());
     }
 }
@@ -0,0 +1,259 @@
+use std::io;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::{fmt, mem};
+
+use crate::bindings::*;
+use crate::rdma::mr::*;
+use crate::rdma::pd::Pd;
+use crate::rdma::type_alias::*;
+use crate::utils::interop::from_c_ret;
+
+use super::{build_sgl, Qp, QpBuilder, QpCreationError, QpType};
+
+/// Queue pair that supports the extended work request API of rdma-core,
+/// created by [`QpBuilder::build_ex()`].
+///
+/// Instead of building `ibv_send_wr` structures and posting them with
+/// `ibv_post_send`, work requests are written directly into the send queue
+/// between [`QpEx::wr_start()`] and [`WrBatch::wr_complete()`], which rings
+/// the doorbell once for the whole batch. This skips the intermediate
+/// structures and their parsing in the provider, and is the recommended fast
+/// path on rdma-core.
+///
+/// Only RC QPs are supported, with RDMA write, read, and send (with or
+/// without immediate). The QP dereferences to [`Qp`] for connection setup
+/// and the legacy posting methods, which can still be used outside batches.
+pub struct QpEx {
+    /// The queue pair.
+    qp: Qp,
+
+    /// Extended view of the queue pair, owned by `qp`.
+    qpx: NonNull<ibv_qp_ex>,
+}
+
+// SAFETY: `qpx` is owned by `qp`, and work requests are only written into it
+// through `&mut self`.
+unsafe impl Send for QpEx {}
+
+impl fmt::Debug for QpEx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("QpEx<{:p}>", self.as_raw()))
+    }
+}
+
+impl QpEx {
+    /// Send operations enabled on extended QPs.
+    const SEND_OPS_FLAGS: u64 = (ibv_qp_create_send_ops_flags::IBV_QP_EX_WITH_RDMA_WRITE.0
+        | ibv_qp_create_send_ops_flags::IBV_QP_EX_WITH_RDMA_WRITE_WITH_IMM.0
+        | ibv_qp_create_send_ops_flags::IBV_QP_EX_WITH_RDMA_READ.0
+        | ibv_qp_create_send_ops_flags::IBV_QP_EX_WITH_SEND.0
+        | ibv_qp_create_send_ops_flags::IBV_QP_EX_WITH_SEND_WITH_IMM.0)
+        as u64;
+
+    /// Create a new extended queue pair with the given builder.
+    pub(super) fn new(pd: &Pd, mut builder: QpBuilder) -> Result<Self, QpCreationError> {
+        if builder.qp_type.is_some_and(|ty| ty != QpType::Rc) {
+            return Err(QpCreationError::UnsupportedFeature(
+                "extended work request API on non-RC QPs",
+            ));
+        }
+
+        builder.send_ops_flags = Self::SEND_OPS_FLAGS;
+        let qp = Qp::new(pd, builder)?;
+
+        // SAFETY: FFI.
+        let qpx = unsafe { ibv_qp_to_qp_ex(qp.as_raw()) };
+        let qpx = NonNull::new(qpx).ok_or(QpCreationError::UnsupportedFeature(
+            "extended work request API",
+        ))?;
+        Ok(Self { qp, qpx })
+    }
+
+    /// Get the underlying [`Qp`], discarding the extended view.
+    #[inline]
+    pub fn into_qp(self) -> Qp {
+        self.qp
+    }
+
+    /// Start a batch of work requests. Nothing is posted until
+    /// [`WrBatch::wr_complete()`] is called, and the batch is discarded if
+    /// it is dropped without being completed.
+    pub fn wr_start(&mut self) -> WrBatch<'_> {
+        let qpx = self.qpx.as_ptr();
+        // SAFETY: FFI.
+        unsafe { ibv_wr_start(qpx) };
+        WrBatch {
+            qpx,
+            qp: &self.qp,
+            sq_sig_all: self.qp.sq_sig_all(),
+            posted: 0,
+            signaled: Vec::new(),
+            finished: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Deref for QpEx {
+    type Target = Qp;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.qp
+    }
+}
+
+impl DerefMut for QpEx {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.qp
+    }
+}
+
+/// A batch of work requests being written into the send queue of a [`QpEx`],
+/// created by [`QpEx::wr_start()`].
+///
+/// Work requests are appended with the chainable [`WrBatch::write()`],
+/// [`WrBatch::read()`] and [`WrBatch::send()`] methods, and posted together
+/// with [`WrBatch::wr_complete()`]. Like the legacy posting methods of
+/// [`Qp`], it is the caller's responsibility to keep the local buffers alive
+/// and to ensure the completion of the work requests, for example by polling
+/// the send CQ.
+pub struct WrBatch<'q> {
+    qpx: *mut ibv_qp_ex,
+    qp: &'q Qp,
+    sq_sig_all: bool,
+
+    /// Number of work requests appended, and the 1-based positions of the
+    /// signaled ones, for send queue tracking.
+    posted: u64,
+    signaled: Vec<u64>,
+
+    finished: bool,
+    _marker: PhantomData<&'q mut QpEx>,
+}
+
+impl WrBatch<'_> {
+    /// Set the ID and flags of the next work request.
+    #[inline]
+    fn set_wr_attrs(&mut self, wr_id: WrId, signal: bool) {
+        self.posted += 1;
+        if signal || self.sq_sig_all {
+            self.signaled.push(self.posted);
+        }
+
+        // SAFETY: `qpx` is valid during the batch.
+        unsafe {
+            (*self.qpx).wr_id = wr_id;
+            (*self.qpx).wr_flags = if signal || self.sq_sig_all {
+                ibv_send_flags::IBV_SEND_SIGNALED.0
+            } else {
+                0
+            };
+        }
+    }
+
+    /// Set the SGL of the last appended work request.
+    #[inline]
+    fn set_sgl(&mut self, local: &[MrSlice]) {
+        // SAFETY: FFI; the provider copies the SGL into the send queue.
+        unsafe {
+            match local {
+                [] => ibv_wr_set_sge_list(self.qpx, 0, std::ptr::null()),
+                [slice] => ibv_wr_set_sge(self.qpx, slice.lkey(), slice.iova(), slice.len() as _),
+                _ => {
+                    let mut sgl = build_sgl(local);
+                    ibv_wr_set_sge_list(self.qpx, local.len(), sgl.as_mut_ptr())
+                }
+            }
+        }
+    }
+
+    /// Append an RDMA write that writes `local` to `remote`, optionally with
+    /// immediate data.
+    pub fn write(
+        &mut self,
+        local: &[MrSlice],
+        remote: &MrRemote,
+        wr_id: WrId,
+        imm: Option<ImmData>,
+        signal: bool,
+    ) -> &mut Self {
+        self.set_wr_attrs(wr_id, signal);
+        // SAFETY: FFI.
+        unsafe {
+            match imm {
+                Some(imm) => ibv_wr_rdma_write_imm(self.qpx, remote.rkey, remote.addr, imm),
+                None => ibv_wr_rdma_write(self.qpx, remote.rkey, remote.addr),
+            }
+        }
+        self.set_sgl(local);
+        self
+    }
+
+    /// Append an RDMA read that reads `remote` into `local`.
+    pub fn read(
+        &mut self,
+        local: &[MrSlice],
+        remote: &MrRemote,
+        wr_id: WrId,
+        signal: bool,
+    ) -> &mut Self {
+        self.set_wr_attrs(wr_id, signal);
+        // SAFETY: FFI.
+        unsafe { ibv_wr_rdma_read(self.qpx, remote.rkey, remote.addr) };
+        self.set_sgl(local);
+        self
+    }
+
+    /// Append an RDMA send of `local`, optionally with immediate data.
+    pub fn send(
+        &mut self,
+        local: &[MrSlice],
+        imm: Option<ImmData>,
+        wr_id: WrId,
+        signal: bool,
+    ) -> &mut Self {
+        self.set_wr_attrs(wr_id, signal);
+        // SAFETY: FFI.
+        unsafe {
+            match imm {
+                Some(imm) => ibv_wr_send_imm(self.qpx, imm),
+                None => ibv_wr_send(self.qpx),
+            }
+        }
+        self.set_sgl(local);
+        self
+    }
+
+    /// Post all work requests in the batch.
+    ///
+    /// If any appended work request was invalid, e.g., exceeding the send
+    /// queue depth, no work request in the batch is posted and the error is
+    /// returned.
+    pub fn wr_complete(mut self) -> io::Result<()> {
+        self.finished = true;
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_wr_complete(self.qpx) };
+        if ret == 0 {
+            self.qp.ex_send_posted(self.posted, &self.signaled);
+        }
+        from_c_ret(ret)
+    }
+
+    /// Discard all work requests in the batch. Equivalent to dropping it.
+    pub fn wr_abort(self) {
+        mem::drop(self);
+    }
+}
+
+impl Drop for WrBatch<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // SAFETY: FFI.
+            unsafe { ibv_wr_abort(self.qpx) };
+        }
+    }
+}
@@ -3,14 +3,25 @@ use std::ptr::NonNull;
 use std::sync::Arc;
 use std::{fmt, mem};
 
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
 use crate::bindings::*;
 #[cfg(mlnx4)]
 use crate::rdma::dct::Dct;
-use crate::rdma::{gid::Gid, pd::Pd, qp::Qp, type_alias::*};
+use crate::rdma::{gid::Gid, nic::PortMtu, pd::Pd, qp::Qp, type_alias::*};
 use crate::utils::interop::from_c_ret;
 
 /// Endpoint (NIC port & queue pair / DCT) data.
-#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+///
+/// Endpoints compare and hash by all of their fields, so they can be used as
+/// keys of connection tables and address handle caches. Equal endpoints stay
+/// equal after a serialization round trip.
+///
+/// With the `zeroize` feature, endpoints can be wiped with `zeroize()` once
+/// the connection is set up. This is not done automatically on drop, as the
+/// type is `Copy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct QpEndpoint {
     /// Endpoint GID.
     pub gid: Option<Gid>,
@@ -23,6 +34,30 @@ pub struct QpEndpoint {
 
     /// QP or DCT number.
     pub num: Qpn,
+
+    /// Initial packet sequence number of the QP's send queue, which the peer
+    /// expects on its receive queue. Endpoints from peers unaware of this
+    /// field default to [`Qp::GLOBAL_INIT_PSN`].
+    #[serde(default)]
+    pub psn: Psn,
+
+    /// Active MTU of the endpoint's port, which caps the path MTU of the
+    /// connection. `None` if unknown, e.g., for endpoints from peers unaware
+    /// of this field, in which case only the local MTU is used.
+    #[serde(default)]
+    pub mtu: Option<PortMtu>,
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for QpEndpoint {
+    fn zeroize(&mut self) {
+        self.gid.zeroize();
+        self.lid.zeroize();
+        self.port_num.zeroize();
+        self.num.zeroize();
+        self.psn.zeroize();
+        self.mtu = None;
+    }
 }
 
 impl QpEndpoint {
@@ -37,6 +72,8 @@ impl QpEndpoint {
                 port_num: port.num(),
                 lid: port.lid(),
                 num: qp.qp_num(),
+                psn: qp.init_psn(),
+                mtu: Some(port.active_mtu()),
             })
         } else {
             Some(Self {
@@ -44,6 +81,8 @@ impl QpEndpoint {
                 port_num: port.num(),
                 lid: port.lid(),
                 num: qp.qp_num(),
+                psn: qp.init_psn(),
+                mtu: Some(port.active_mtu()),
             })
         }
     }
@@ -58,16 +97,27 @@ impl QpEndpoint {
             port_num: init_attr.port.num(),
             lid: init_attr.port.lid(),
             num: dct.dct_num(),
+            psn: Qp::GLOBAL_INIT_PSN,
+            mtu: Some(init_attr.port.active_mtu()),
         }
     }
 
+    /// Create a builder for an endpoint with user-designated routing
+    /// information, which only requires the QP number to be set.
+    pub fn builder() -> QpEndpointBuilder {
+        QpEndpointBuilder::new()
+    }
+
     /// Create a new endpoint with user-designated routing information.
+    /// The PSN is set to [`Qp::GLOBAL_INIT_PSN`], and the MTU is unknown.
     pub fn new(gid: Option<Gid>, lid: Lid, port_num: PortNum, num: Qpn) -> Self {
         Self {
             gid,
             lid,
             port_num,
             num,
+            psn: Qp::GLOBAL_INIT_PSN,
+            mtu: None,
         }
     }
 
@@ -82,6 +132,100 @@ impl QpEndpoint {
     }
 }
 
+/// Builder of [`QpEndpoint`], useful for constructing endpoints from routing
+/// information obtained by custom discovery mechanisms.
+///
+/// Unspecified fields take the following defaults:
+///
+/// - no GID, i.e., local routing;
+/// - LID 0, which is the case for RoCE ports;
+/// - port number 1, i.e., the first port of the device;
+/// - PSN [`Qp::GLOBAL_INIT_PSN`];
+/// - unknown MTU.
+///
+/// The Q_Key is not part of the endpoint, as all QPs use [`Qp::GLOBAL_QKEY`].
+#[derive(Debug, Clone, Copy)]
+pub struct QpEndpointBuilder {
+    gid: Option<Gid>,
+    lid: Lid,
+    port_num: PortNum,
+    num: Option<Qpn>,
+    psn: Psn,
+    mtu: Option<PortMtu>,
+}
+
+impl QpEndpointBuilder {
+    /// Create a new endpoint builder.
+    pub fn new() -> Self {
+        Self {
+            gid: None,
+            lid: 0,
+            port_num: 1,
+            num: None,
+            psn: Qp::GLOBAL_INIT_PSN,
+            mtu: None,
+        }
+    }
+
+    /// Set the endpoint GID, enabling global routing.
+    pub fn gid(mut self, gid: Gid) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Set the port LID.
+    pub fn lid(mut self, lid: Lid) -> Self {
+        self.lid = lid;
+        self
+    }
+
+    /// Set the port index.
+    pub fn port_num(mut self, port_num: PortNum) -> Self {
+        self.port_num = port_num;
+        self
+    }
+
+    /// Set the QP or DCT number.
+    pub fn num(mut self, num: Qpn) -> Self {
+        self.num = Some(num);
+        self
+    }
+
+    /// Set the initial packet sequence number of the QP.
+    pub fn psn(mut self, psn: Psn) -> Self {
+        self.psn = psn;
+        self
+    }
+
+    /// Set the active MTU of the endpoint's port.
+    pub fn mtu(mut self, mtu: PortMtu) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Build the endpoint.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the QP number is not set.
+    pub fn build(self) -> QpEndpoint {
+        QpEndpoint {
+            gid: self.gid,
+            lid: self.lid,
+            port_num: self.port_num,
+            num: self.num.expect("QP number must be explicitly set"),
+            psn: self.psn,
+            mtu: self.mtu,
+        }
+    }
+}
+
+impl Default for QpEndpointBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Wrapper of [`*mut ibv_ah`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) struct IbvAh(Option<NonNull<ibv_ah>>);
@@ -165,6 +309,7 @@ impl QpPeer {
             };
         }
 
+        let _guard = pd.context().lock_creation();
         // SAFETY: FFI.
         let ah = unsafe { ibv_create_ah(pd.as_raw(), &mut ah_attr) };
         let ah = NonNull::new(ah).ok_or_else(IoError::last_os_error)?;
@@ -181,6 +326,27 @@ impl QpPeer {
         })
     }
 
+    /// Recreate the address handle of this peer with a new source GID index,
+    /// keeping the endpoint unchanged.
+    ///
+    /// An address handle refers to a local GID by its index in the port's GID
+    /// table. If the table changes, e.g., due to network reconfiguration, the
+    /// index may become invalid or point to another GID, and sends through
+    /// the handle fail or are misrouted without further notice. Long-lived
+    /// UD or DC servers should therefore rebuild their peers after
+    /// [`Context::query_port()`] shows that the GID table has changed, or
+    /// upon [`PortEvent`]s.
+    ///
+    /// The old address handle is destroyed when it is no longer used by any
+    /// clone of this peer, which are not updated.
+    ///
+    /// [`Context::query_port()`]: crate::rdma::context::Context::query_port
+    /// [`PortEvent`]: crate::rdma::nic::PortEvent
+    pub fn rebuild(&mut self, pd: &Pd, new_gid_index: GidIndex) -> io::Result<()> {
+        *self = Self::new(pd, new_gid_index, self.inner.ep)?;
+        Ok(())
+    }
+
     /// Return a handle that can be used in RDMA UD sends to this peer.
     /// The return type is opaque to the user; you may only copy assign it to [`ibv_send_wr::wr`].
     #[inline]
@@ -218,3 +384,47 @@ impl QpPeer {
         wr.wr.ud = self.ud();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(ep: QpEndpoint) {
+        let json = serde_json::to_string(&ep).unwrap();
+        assert_eq!(serde_json::from_str::<QpEndpoint>(&json).unwrap(), ep);
+    }
+
+    #[test]
+    fn endpoint_round_trip() {
+        let gid = Gid::from([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        for gid in [Some(gid), None] {
+            for mtu in [Some(PortMtu::Mtu1024), None] {
+                let mut builder = QpEndpointBuilder::new()
+                    .lid(7)
+                    .port_num(2)
+                    .num(0x1234)
+                    .psn(0xabcdef);
+                if let Some(gid) = gid {
+                    builder = builder.gid(gid);
+                }
+                if let Some(mtu) = mtu {
+                    builder = builder.mtu(mtu);
+                }
+                round_trip(builder.build());
+            }
+        }
+    }
+
+    #[test]
+    fn endpoint_defaults_missing_fields() {
+        let ep = QpEndpoint::new(None, 7, 2, 0x1234);
+        let mut json = serde_json::to_value(ep).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        fields.remove("psn");
+        fields.remove("mtu");
+
+        let ep = serde_json::from_value::<QpEndpoint>(json).unwrap();
+        assert_eq!(ep.psn, Qp::GLOBAL_INIT_PSN);
+        assert_eq!(ep.mtu, None);
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::Qp;
+
+/// Shareable handle of a queue pair, created by [`Qp::share()`].
+///
+/// A `SharedQp` only dereferences to `&Qp`, so it exposes the data-plane
+/// methods (e.g., posting work requests) but not the connection-setup ones
+/// that require `&mut Qp`, such as [`Qp::bind_peer()`] and [`Qp::reset()`].
+/// The queue pair is destroyed when the last handle is dropped.
+#[derive(Clone)]
+pub struct SharedQp(Arc<Qp>);
+
+impl SharedQp {
+    /// Try to get back the exclusive `Qp`, which succeeds only if this is the
+    /// last handle. Otherwise, return the handle itself.
+    pub fn try_unwrap(self) -> Result<Qp, Self> {
+        Arc::try_unwrap(self.0).map_err(Self)
+    }
+}
+
+impl Deref for SharedQp {
+    type Target = Qp;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SharedQp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("SharedQp<{:p}>", self.as_raw()))
+    }
+}
+
+impl From<Qp> for SharedQp {
+    fn from(qp: Qp) -> Self {
+        Self(Arc::new(qp))
+    }
+}
@@ -43,6 +43,10 @@ impl QpType {
     const fn is_target_impl(self) -> bool {
         !matches!(self, Self::XrcIni | Self::DcIni)
     }
+
+    const fn requires_ah_per_send_impl(self) -> bool {
+        matches!(self, Self::Ud | Self::DcIni)
+    }
 }
 
 #[cfg(mlnx5)]
@@ -54,6 +58,11 @@ impl QpType {
     const fn is_target_impl(self) -> bool {
         !matches!(self, Self::XrcIni)
     }
+
+    const fn requires_ah_per_send_impl(self) -> bool {
+        // DC initiators are created as driver QPs.
+        matches!(self, Self::Ud | Self::Driver)
+    }
 }
 
 impl QpType {
@@ -62,7 +71,11 @@ impl QpType {
         self.is_reliable_impl()
     }
 
-    /// Determine whether the QP type is datagram.
+    /// Determine whether the QP type is connection-oriented, i.e., not
+    /// datagram (UD or raw packet).
+    ///
+    /// DC initiators count as connected, although they do not have a fixed
+    /// peer; see [`QpType::has_fixed_peer()`].
     pub const fn is_connected(self) -> bool {
         !matches!(self, Self::Ud | Self::RawPacket)
     }
@@ -77,10 +90,26 @@ impl QpType {
         self.is_target_impl()
     }
 
-    /// Determine whether the QP type has a fixed peer.
+    /// Determine whether the QP type has a fixed peer, i.e., it talks to the
+    /// single peer it is connected to on bring-up.
+    ///
+    /// This is the case for RC, UC and XRC initiator QPs. For them, the
+    /// `peer` argument of send-type verbs is ignored. UD QPs and DC
+    /// initiators instead take the destination from every send, and return
+    /// an error if neither a `peer` argument nor a default peer is given.
     pub const fn has_fixed_peer(self) -> bool {
         matches!(self, Self::Rc | Self::Uc | Self::XrcIni)
     }
+
+    /// Determine whether every send-type work request of the QP type must
+    /// carry an address handle of its destination, i.e., a [`QpPeer`].
+    ///
+    /// This is the case for UD QPs and DC initiators.
+    ///
+    /// [`QpPeer`]: crate::rdma::qp::QpPeer
+    pub const fn requires_ah_per_send(self) -> bool {
+        self.requires_ah_per_send_impl()
+    }
 }
 
 impl From<QpType> for u32 {
@@ -75,6 +75,7 @@ impl Srq {
                 },
             };
 
+            let _guard = pd.context().lock_creation();
             // SAFETY: FFI.
             let srq = unsafe { ibv_create_srq(pd.as_raw(), &mut init_attr) };
             let srq = NonNull::new(srq).ok_or_else(IoError::last_os_error)?;
@@ -121,6 +122,31 @@ impl Srq {
         self.inner.num
     }
 
+    /// Arm the SRQ limit. Once the number of receive requests posted to the
+    /// SRQ drops below `limit`, the device reports an
+    /// [`AsyncEvent::SrqLimitReached`](crate::rdma::context::AsyncEvent::SrqLimitReached)
+    /// through [`Context::async_events()`], which is the signal to post more
+    /// receive buffers. The limit is then disarmed, and must be set again to
+    /// get another event. A limit of 0 disarms it.
+    ///
+    /// Fail with `InvalidInput` if the limit exceeds the SRQ capacity.
+    pub fn set_limit(&self, limit: u32) -> io::Result<()> {
+        let mut attr = ibv_srq_attr {
+            max_wr: 0,
+            max_sge: 0,
+            srq_limit: limit,
+        };
+        // SAFETY: FFI.
+        let ret = unsafe {
+            ibv_modify_srq(
+                self.as_raw(),
+                &mut attr,
+                ibv_srq_attr_mask::IBV_SRQ_LIMIT.0 as _,
+            )
+        };
+        from_c_ret(ret)
+    }
+
     /// Post a receive work request to the SRQ.
     pub fn recv(&self, local: &[MrSlice], wr_id: u64) -> io::Result<()> {
         assert!(local.len() == 1);
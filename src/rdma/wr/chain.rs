@@ -0,0 +1,191 @@
+use std::marker::PhantomData;
+use std::{io, ptr};
+
+use super::{RecvWr, SendWr, SingleRecvWr, SingleSendWr};
+use crate::bindings::*;
+use crate::rdma::qp::Qp;
+use crate::utils::interop::from_c_ret;
+
+/// A chain of borrowed send work requests, posted with a single
+/// `ibv_post_send` call.
+///
+/// The chain links the work requests in place through their `next` pointers,
+/// without copying them or allocating. It mutably borrows every linked work
+/// request for its lifetime `'c`, so that none of them can be moved, modified
+/// or dropped while the chain may still be posted. Dropping the chain unlinks
+/// the work requests again.
+pub struct SendWrChain<'c> {
+    head: *mut ibv_send_wr,
+    tail: *mut ibv_send_wr,
+    _marker: PhantomData<&'c mut ibv_send_wr>,
+}
+
+impl<'c> SendWrChain<'c> {
+    /// Create an empty chain.
+    pub fn new() -> Self {
+        Self {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Append a work request to the end of the chain.
+    /// Any `next` link previously set on the work request is overwritten.
+    pub fn push<const N: usize>(&mut self, wr: &'c mut SendWr<'_, N>) -> &mut Self {
+        self.link(wr.as_mut_ptr())
+    }
+
+    /// Append a single-SGE work request to the end of the chain.
+    /// Any `next` link previously set on the work request is overwritten.
+    pub fn push_single(&mut self, wr: &'c mut SingleSendWr<'_>) -> &mut Self {
+        self.link(wr.as_mut_ptr())
+    }
+
+    /// Link a borrowed work request to the end of the chain.
+    fn link(&mut self, wr: *mut ibv_send_wr) -> &mut Self {
+        // SAFETY: `wr` and `tail` are borrowed for `'c`.
+        unsafe {
+            (*wr).next = ptr::null_mut();
+            if self.tail.is_null() {
+                self.head = wr;
+            } else {
+                (*self.tail).next = wr;
+            }
+        }
+        self.tail = wr;
+        self
+    }
+
+    /// Return `true` if the chain is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    /// Post all work requests in the chain to the send queue.
+    /// Posting an empty chain is a no-op.
+    ///
+    /// **NOTE:** unlike [`SendWr::post_on()`], this method does not check the
+    /// work requests against QP capabilities such as IP checksum offload.
+    pub fn post_on(&mut self, qp: &Qp) -> io::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut bad_wr = ptr::null_mut();
+        // SAFETY: FFI, and all linked work requests are alive.
+        let ret = unsafe { ibv_post_send(qp.as_raw(), self.head, &mut bad_wr) };
+        qp.send_posted(self.head, bad_wr);
+        from_c_ret(ret)
+    }
+}
+
+impl Default for SendWrChain<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SendWrChain<'_> {
+    fn drop(&mut self) {
+        // Unlink the work requests, so that none of them keeps a dangling
+        // `next` pointer to another once the borrows end.
+        let mut wr = self.head;
+        while !wr.is_null() {
+            // SAFETY: all linked work requests are borrowed for `'c`.
+            unsafe {
+                let next = (*wr).next;
+                (*wr).next = ptr::null_mut();
+                wr = next;
+            }
+        }
+    }
+}
+
+/// A chain of borrowed receive work requests, posted with a single
+/// `ibv_post_recv` call. See [`SendWrChain`] for details.
+pub struct RecvWrChain<'c> {
+    head: *mut ibv_recv_wr,
+    tail: *mut ibv_recv_wr,
+    _marker: PhantomData<&'c mut ibv_recv_wr>,
+}
+
+impl<'c> RecvWrChain<'c> {
+    /// Create an empty chain.
+    pub fn new() -> Self {
+        Self {
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Append a work request to the end of the chain.
+    /// Any `next` link previously set on the work request is overwritten.
+    pub fn push<const N: usize>(&mut self, wr: &'c mut RecvWr<'_, N>) -> &mut Self {
+        self.link(wr.as_mut_ptr())
+    }
+
+    /// Append a single-SGE work request to the end of the chain.
+    /// Any `next` link previously set on the work request is overwritten.
+    pub fn push_single(&mut self, wr: &'c mut SingleRecvWr<'_>) -> &mut Self {
+        self.link(wr.as_mut_ptr())
+    }
+
+    /// Link a borrowed work request to the end of the chain.
+    fn link(&mut self, wr: *mut ibv_recv_wr) -> &mut Self {
+        // SAFETY: `wr` and `tail` are borrowed for `'c`.
+        unsafe {
+            (*wr).next = ptr::null_mut();
+            if self.tail.is_null() {
+                self.head = wr;
+            } else {
+                (*self.tail).next = wr;
+            }
+        }
+        self.tail = wr;
+        self
+    }
+
+    /// Return `true` if the chain is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.is_null()
+    }
+
+    /// Post all work requests in the chain to the receive queue.
+    /// Posting an empty chain is a no-op.
+    pub fn post(&mut self, qp: &Qp) -> io::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut bad_wr = ptr::null_mut();
+        // SAFETY: FFI, and all linked work requests are alive.
+        let ret = unsafe { ibv_post_recv(qp.as_raw(), self.head, &mut bad_wr) };
+        qp.recv_posted(self.head, bad_wr);
+        from_c_ret(ret)
+    }
+}
+
+impl Default for RecvWrChain<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for RecvWrChain<'_> {
+    fn drop(&mut self) {
+        // See the `Drop` implementation of `SendWrChain`.
+        let mut wr = self.head;
+        while !wr.is_null() {
+            // SAFETY: all linked work requests are borrowed for `'c`.
+            unsafe {
+                let next = (*wr).next;
+                (*wr).next = ptr::null_mut();
+                wr = next;
+            }
+        }
+    }
+}
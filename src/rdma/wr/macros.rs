@@ -38,7 +38,7 @@ macro_rules! impl_wr_basic_setters {
                 mr_slice: &$crate::rdma::mr::MrSlice<'a>,
             ) -> &mut Self {
                 self.sgl[index] = ibv_sge {
-                    addr: mr_slice.addr() as _,
+                    addr: mr_slice.iova(),
                     length: mr_slice.len() as _,
                     lkey: mr_slice.lkey(),
                 };
@@ -47,12 +47,37 @@ macro_rules! impl_wr_basic_setters {
             }
         }
     };
+    ($wr:tt<$lt:lifetime>) => {
+        impl<$lt> $wr<$lt> {
+            /// Set the work request ID.
+            pub fn set_id(&mut self, wr_id: u64) -> &mut Self {
+                self.wr.wr_id = wr_id;
+                self
+            }
+
+            /// Set the SGE.
+            pub fn set_sge(&mut self, mr_slice: &$crate::rdma::mr::MrSlice<$lt>) -> &mut Self {
+                self.sge = ibv_sge {
+                    addr: mr_slice.iova(),
+                    length: mr_slice.len() as _,
+                    lkey: mr_slice.lkey(),
+                };
+                self
+            }
+        }
+    };
 }
 
 #[allow(unused_macros)]
 macro_rules! impl_wr_flags_setters {
     ($wr:tt, $flags:tt) => {
-        impl<'a, const N: usize> $wr<'a, N> {
+        impl_wr_flags_setters!(@impl [<'a, const N: usize>] [$wr<'a, N>], $flags);
+    };
+    ($wr:tt<$lt:lifetime>, $flags:tt) => {
+        impl_wr_flags_setters!(@impl [<$lt>] [$wr<$lt>], $flags);
+    };
+    (@impl [$($generics:tt)*] [$($ty:tt)*], $flags:tt) => {
+        impl $($generics)* $($ty)* {
             /// Set the work request flags.
             pub fn set_flags(&mut self, flags: u32) -> &mut Self {
                 self.wr.$flags = flags;
@@ -76,6 +101,24 @@ macro_rules! impl_wr_flags_setters {
                 self.wr.$flags |= $crate::bindings::ibv_send_flags::IBV_SEND_INLINE.0;
                 self
             }
+
+            /// Set the work request flags to include `IBV_SEND_FENCE`, so that
+            /// it is not processed until all prior RDMA reads and atomics on
+            /// the same QP have completed.
+            pub fn set_flag_fence(&mut self) -> &mut Self {
+                self.wr.$flags |= $crate::bindings::ibv_send_flags::IBV_SEND_FENCE.0;
+                self
+            }
+
+            /// Set the work request flags to include `IBV_SEND_IP_CSUM`.
+            ///
+            /// Only UD and raw packet QPs on devices with the corresponding
+            /// checksum offload capability accept this flag. See
+            /// [`Qp::supports_ip_csum_offload()`](crate::rdma::qp::Qp::supports_ip_csum_offload).
+            pub fn set_flag_ip_csum(&mut self) -> &mut Self {
+                self.wr.$flags |= $crate::bindings::ibv_send_flags::IBV_SEND_IP_CSUM.0;
+                self
+            }
         }
     };
 }
@@ -110,3 +153,98 @@ macro_rules! impl_wr_raw_accessors {
         }
     };
 }
+
+#[allow(unused_macros)]
+macro_rules! impl_send_wr_opcode_setters {
+    ($wr:tt) => {
+        impl_send_wr_opcode_setters!(@impl [<'a, const N: usize>] [$wr<'a, N>]);
+    };
+    ($wr:tt<$lt:lifetime>) => {
+        impl_send_wr_opcode_setters!(@impl [<$lt>] [$wr<$lt>]);
+    };
+    (@impl [$($generics:tt)*] [$($ty:tt)*]) => {
+        impl $($generics)* $($ty)* {
+            /// Set the work request to an RDMA send.
+            #[inline]
+            pub fn set_wr_send(&mut self, imm: Option<u32>) -> &mut Self {
+                match imm {
+                    Some(imm) => {
+                        self.wr.opcode = ibv_wr_opcode::IBV_WR_SEND_WITH_IMM;
+                        self.wr.set_imm(imm);
+                    }
+                    None => self.wr.opcode = ibv_wr_opcode::IBV_WR_SEND,
+                };
+                self
+            }
+
+            /// Set the work request to an RDMA read.
+            #[inline]
+            pub fn set_wr_read(&mut self, remote: $crate::rdma::mr::MrRemote) -> &mut Self {
+                self.wr.opcode = ibv_wr_opcode::IBV_WR_RDMA_READ;
+                self.wr.wr.rdma = (&remote).into();
+                self
+            }
+
+            /// Set the work request to an RDMA write.
+            #[inline]
+            pub fn set_wr_write(
+                &mut self,
+                remote: $crate::rdma::mr::MrRemote,
+                imm: Option<u32>,
+            ) -> &mut Self {
+                self.wr.wr.rdma = (&remote).into();
+                match imm {
+                    Some(imm) => {
+                        self.wr.opcode = ibv_wr_opcode::IBV_WR_RDMA_WRITE_WITH_IMM;
+                        self.wr.set_imm(imm);
+                    }
+                    None => self.wr.opcode = ibv_wr_opcode::IBV_WR_RDMA_WRITE,
+                };
+                self
+            }
+
+            /// Set the work request to an RDMA atomic compare-and-swap.
+            #[inline]
+            pub fn set_wr_cas(
+                &mut self,
+                remote: $crate::rdma::mr::MrRemote,
+                compare: u64,
+                swap: u64,
+            ) -> &mut Self {
+                self.wr.opcode = ibv_wr_opcode::IBV_WR_ATOMIC_CMP_AND_SWP;
+                self.wr.wr.atomic = atomic_t {
+                    remote_addr: remote.addr,
+                    compare_add: compare,
+                    swap,
+                    rkey: remote.rkey,
+                };
+                self
+            }
+
+            /// Set the work request to an RDMA atomic fetch-and-add.
+            #[inline]
+            pub fn set_wr_faa(&mut self, remote: $crate::rdma::mr::MrRemote, add: u64) -> &mut Self {
+                self.wr.opcode = ibv_wr_opcode::IBV_WR_ATOMIC_FETCH_AND_ADD;
+                self.wr.wr.atomic = atomic_t {
+                    remote_addr: remote.addr,
+                    compare_add: add,
+                    swap: 0,
+                    rkey: remote.rkey,
+                };
+                self
+            }
+
+            /// Return `true` if the work request is an RDMA read or atomic,
+            /// i.e., one that a later fenced work request waits for.
+            #[inline]
+            pub fn is_read_or_atomic(&self) -> bool {
+                matches!(
+                    self.wr.opcode,
+                    ibv_wr_opcode::IBV_WR_RDMA_READ
+                        | ibv_wr_opcode::IBV_WR_ATOMIC_CMP_AND_SWP
+                        | ibv_wr_opcode::IBV_WR_ATOMIC_FETCH_AND_ADD
+                )
+            }
+        }
+    };
+}
@@ -1,7 +1,11 @@
 //! Work request wrappers.
 
+mod chain;
 mod recv;
 mod send;
+mod single;
 
+pub use self::chain::*;
 pub use self::recv::*;
 pub use self::send::*;
+pub use self::single::*;
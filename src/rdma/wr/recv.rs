@@ -45,6 +45,7 @@ impl<const N: usize> RecvWr<'_, N> {
         let mut bad_wr = std::ptr::null_mut();
         // SAFETY: FFI.
         let ret = unsafe { ibv_post_recv(qp.as_raw(), &mut self.wr, &mut bad_wr) };
+        qp.recv_posted(&self.wr, bad_wr);
         from_c_ret(ret)
     }
 }
@@ -1,6 +1,7 @@
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
 use std::marker::PhantomData;
+use std::mem;
 use std::pin::Pin;
-use std::{io, mem};
 
 use crate::bindings::*;
 use crate::rdma::{mr::*, qp::Qp};
@@ -38,75 +39,44 @@ impl<const N: usize> Default for SendWr<'_, N> {
 impl_wr_basic_setters!(SendWr);
 impl_wr_flags_setters!(SendWr, send_flags);
 impl_wr_raw_accessors!(SendWr, ibv_send_wr);
+impl_send_wr_opcode_setters!(SendWr);
 
 impl<const N: usize> SendWr<'_, N> {
-    /// Set the work request to an RDMA send.
-    #[inline]
-    pub fn set_wr_send(&mut self, imm: Option<u32>) -> &mut Self {
-        match imm {
-            Some(imm) => {
-                self.wr.opcode = ibv_wr_opcode::IBV_WR_SEND_WITH_IMM;
-                self.wr.set_imm(imm);
-            }
-            None => self.wr.opcode = ibv_wr_opcode::IBV_WR_SEND,
-        };
-        self
-    }
-
-    /// Set the work request to an RDMA read.
-    #[inline]
-    pub fn set_wr_read(&mut self, remote: MrRemote) -> &mut Self {
-        self.wr.opcode = ibv_wr_opcode::IBV_WR_RDMA_READ;
-        self.wr.wr.rdma = (&remote).into();
-        self
-    }
-
-    /// Set the work request to an RDMA write.
-    #[inline]
-    pub fn set_wr_write(&mut self, remote: MrRemote, imm: Option<u32>) -> &mut Self {
-        self.wr.wr.rdma = (&remote).into();
-        match imm {
-            Some(imm) => {
-                self.wr.opcode = ibv_wr_opcode::IBV_WR_RDMA_WRITE_WITH_IMM;
-                self.wr.set_imm(imm);
-            }
-            None => self.wr.opcode = ibv_wr_opcode::IBV_WR_RDMA_WRITE,
-        };
-        self
-    }
-
-    /// Set the work request to an RDMA atomic compare-and-swap.
-    #[inline]
-    pub fn set_wr_cas(&mut self, remote: MrRemote, compare: u64, swap: u64) -> &mut Self {
-        self.wr.opcode = ibv_wr_opcode::IBV_WR_ATOMIC_CMP_AND_SWP;
-        self.wr.wr.atomic = atomic_t {
-            remote_addr: remote.addr,
-            compare_add: compare,
-            swap,
-            rkey: remote.rkey,
-        };
-        self
-    }
-
-    /// Set the work request to an RDMA atomic fetch-and-add.
-    #[inline]
-    pub fn set_wr_faa(&mut self, remote: MrRemote, add: u64) -> &mut Self {
-        self.wr.opcode = ibv_wr_opcode::IBV_WR_ATOMIC_FETCH_AND_ADD;
-        self.wr.wr.atomic = atomic_t {
-            remote_addr: remote.addr,
-            compare_add: add,
-            swap: 0,
-            rkey: remote.rkey,
-        };
-        self
-    }
-
     /// Post the work request to the send queue.
+    ///
+    /// Return an error if the work request requires IP checksum offload but
+    /// the QP does not support it, or if it is inline but its data exceeds
+    /// [`Qp::inline_capacity()`].
     #[inline]
     pub fn post_on(&mut self, qp: &Qp) -> io::Result<()> {
+        if self.wr.send_flags & ibv_send_flags::IBV_SEND_INLINE.0 != 0 {
+            let len = self.sgl[..self.wr.num_sge as usize]
+                .iter()
+                .map(|sge| sge.length as usize)
+                .sum();
+            qp.check_inline(len)?;
+        }
+
+        if self.wr.send_flags & ibv_send_flags::IBV_SEND_IP_CSUM.0 != 0
+            && !qp.supports_ip_csum_offload()
+        {
+            return Err(IoError::new(
+                IoErrorKind::Unsupported,
+                "IP checksum offload is not supported by this QP type or device",
+            ));
+        }
+
         let mut bad_wr = std::ptr::null_mut();
         // SAFETY: FFI.
         let ret = unsafe { ibv_post_send(qp.as_raw(), &mut self.wr, &mut bad_wr) };
+        qp.send_posted(&self.wr, bad_wr);
+        trace_event!(
+            qpn = qp.qp_num(),
+            wr_id = self.wr.wr_id,
+            opcode = self.wr.opcode,
+            ret,
+            "post send"
+        );
         from_c_ret(ret)
     }
 }
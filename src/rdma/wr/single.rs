@@ -0,0 +1,208 @@
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::marker::PhantomData;
+use std::{mem, ptr};
+
+use super::{RecvWr, SendWr};
+use crate::bindings::*;
+use crate::rdma::{mr::*, qp::Qp};
+use crate::utils::interop::from_c_ret;
+
+include!("macros.rs");
+
+/// The operation of a send work request, i.e., its opcode and the operands
+/// that come with it, as set by the `set_wr_*` methods.
+#[derive(Debug, Clone, Copy)]
+pub enum SendWrDetails {
+    /// An RDMA send, with optional immediate data.
+    Send(Option<u32>),
+
+    /// An RDMA read from the remote memory region slice.
+    Read(MrRemote),
+
+    /// An RDMA write to the remote memory region slice, with optional
+    /// immediate data.
+    Write(MrRemote, Option<u32>),
+
+    /// An RDMA atomic compare-and-swap on the remote memory region slice.
+    Cas {
+        remote: MrRemote,
+        compare: u64,
+        swap: u64,
+    },
+
+    /// An RDMA atomic fetch-and-add on the remote memory region slice.
+    Faa { remote: MrRemote, add: u64 },
+}
+
+impl<'a> SendWr<'a, 1> {
+    /// Create a send work request on the given local memory region slice,
+    /// with the given work request ID and operation, that stores its only SGE
+    /// inline instead of allocating an SGL. See [`SingleSendWr`].
+    pub fn single(slice: &MrSlice<'a>, wr_id: u64, details: SendWrDetails) -> SingleSendWr<'a> {
+        let mut wr = SingleSendWr::new(slice, wr_id);
+        wr.set_details(details);
+        wr
+    }
+}
+
+impl<'a> RecvWr<'a, 1> {
+    /// Create a receive work request on the given local memory region slice,
+    /// with the given work request ID, that stores its only SGE inline
+    /// instead of allocating an SGL. See [`SingleRecvWr`].
+    pub fn single(slice: &MrSlice<'a>, wr_id: u64) -> SingleRecvWr<'a> {
+        SingleRecvWr::new(slice, wr_id)
+    }
+}
+
+/// Send work request with exactly one SGE, stored inline.
+///
+/// Unlike [`SendWr`](super::SendWr), which keeps its SGL in a pinned heap
+/// allocation so that it can be moved freely, this type does not allocate.
+/// The SGL pointer of the underlying `ibv_send_wr` is instead refreshed
+/// whenever the work request is handed out for posting, i.e., by
+/// [`SingleSendWr::post_on()`], [`SingleSendWr::as_mut_ptr()`] and
+/// [`SendWrChain::push_single()`](super::SendWrChain::push_single). This
+/// suits the common single-SGE case in doorbell-batching builders.
+pub struct SingleSendWr<'a> {
+    wr: ibv_send_wr,
+    sge: ibv_sge,
+    _marker: PhantomData<&'a Mr>,
+}
+
+impl_wr_basic_setters!(SingleSendWr<'a>);
+impl_wr_flags_setters!(SingleSendWr<'a>, send_flags);
+impl_send_wr_opcode_setters!(SingleSendWr<'a>);
+
+impl<'a> SingleSendWr<'a> {
+    /// Create a send work request on the given local memory region slice,
+    /// with the given work request ID. The opcode defaults to an RDMA send;
+    /// use the `set_wr_*` methods to change it.
+    pub fn new(slice: &MrSlice<'a>, wr_id: u64) -> Self {
+        // SAFETY: POD type.
+        let mut wr: ibv_send_wr = unsafe { mem::zeroed() };
+        wr.wr_id = wr_id;
+        wr.num_sge = 1;
+        wr.opcode = ibv_wr_opcode::IBV_WR_SEND;
+        let mut this = Self {
+            wr,
+            // SAFETY: POD type.
+            sge: unsafe { mem::zeroed() },
+            _marker: PhantomData,
+        };
+        this.set_sge(slice);
+        this
+    }
+
+    /// Set the operation of the work request.
+    pub fn set_details(&mut self, details: SendWrDetails) -> &mut Self {
+        match details {
+            SendWrDetails::Send(imm) => self.set_wr_send(imm),
+            SendWrDetails::Read(remote) => self.set_wr_read(remote),
+            SendWrDetails::Write(remote, imm) => self.set_wr_write(remote, imm),
+            SendWrDetails::Cas {
+                remote,
+                compare,
+                swap,
+            } => self.set_wr_cas(remote, compare, swap),
+            SendWrDetails::Faa { remote, add } => self.set_wr_faa(remote, add),
+        }
+    }
+
+    /// Get a mutable raw pointer to the work request, with its SGL pointer
+    /// pointing to the inline SGE.
+    ///
+    /// The pointer is only valid as long as the work request is not moved.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut ibv_send_wr {
+        self.wr.sg_list = &mut self.sge;
+        &mut self.wr
+    }
+
+    /// Post the work request to the send queue.
+    ///
+    /// Return an error if the work request requires IP checksum offload but
+    /// the QP does not support it, or if it is inline but its data exceeds
+    /// [`Qp::inline_capacity()`].
+    #[inline]
+    pub fn post_on(&mut self, qp: &Qp) -> io::Result<()> {
+        if self.wr.send_flags & ibv_send_flags::IBV_SEND_INLINE.0 != 0 {
+            qp.check_inline(self.sge.length as usize)?;
+        }
+
+        if self.wr.send_flags & ibv_send_flags::IBV_SEND_IP_CSUM.0 != 0
+            && !qp.supports_ip_csum_offload()
+        {
+            return Err(IoError::new(
+                IoErrorKind::Unsupported,
+                "IP checksum offload is not supported by this QP type or device",
+            ));
+        }
+
+        // Post this work request alone, even if a leaked chain left it linked.
+        self.wr.next = ptr::null_mut();
+        let wr = self.as_mut_ptr();
+        let mut bad_wr = ptr::null_mut();
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_post_send(qp.as_raw(), wr, &mut bad_wr) };
+        qp.send_posted(wr, bad_wr);
+        trace_event!(
+            qpn = qp.qp_num(),
+            wr_id = self.wr.wr_id,
+            opcode = self.wr.opcode,
+            ret,
+            "post send"
+        );
+        from_c_ret(ret)
+    }
+}
+
+/// Receive work request with exactly one SGE, stored inline.
+/// See [`SingleSendWr`] for details.
+pub struct SingleRecvWr<'a> {
+    wr: ibv_recv_wr,
+    sge: ibv_sge,
+    _marker: PhantomData<&'a Mr>,
+}
+
+impl_wr_basic_setters!(SingleRecvWr<'a>);
+
+impl<'a> SingleRecvWr<'a> {
+    /// Create a receive work request on the given local memory region slice,
+    /// with the given work request ID.
+    pub fn new(slice: &MrSlice<'a>, wr_id: u64) -> Self {
+        // SAFETY: POD type.
+        let mut wr: ibv_recv_wr = unsafe { mem::zeroed() };
+        wr.wr_id = wr_id;
+        wr.num_sge = 1;
+        let mut this = Self {
+            wr,
+            // SAFETY: POD type.
+            sge: unsafe { mem::zeroed() },
+            _marker: PhantomData,
+        };
+        this.set_sge(slice);
+        this
+    }
+
+    /// Get a mutable raw pointer to the work request, with its SGL pointer
+    /// pointing to the inline SGE.
+    ///
+    /// The pointer is only valid as long as the work request is not moved.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut ibv_recv_wr {
+        self.wr.sg_list = &mut self.sge;
+        &mut self.wr
+    }
+
+    /// Post the work request to the receive queue.
+    #[inline]
+    pub fn post(&mut self, qp: &Qp) -> io::Result<()> {
+        self.wr.next = ptr::null_mut();
+        let wr = self.as_mut_ptr();
+        let mut bad_wr = ptr::null_mut();
+        // SAFETY: FFI.
+        let ret = unsafe { ibv_post_recv(qp.as_raw(), wr, &mut bad_wr) };
+        qp.recv_posted(wr, bad_wr);
+        from_c_ret(ret)
+    }
+}
@@ -4,3 +4,4 @@
 
 pub(crate) mod boilerplate;
 pub(crate) mod interop;
+pub(crate) mod trace;
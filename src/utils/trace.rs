@@ -0,0 +1,10 @@
+#![macro_use]
+
+/// Record a `tracing` event at the TRACE level with the given fields and
+/// message. Expands to nothing unless feature `tracing` is enabled.
+macro_rules! trace_event {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!($($arg)*);
+    };
+}
@@ -0,0 +1,218 @@
+use std::alloc::{self, Layout};
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::ops::{Deref, DerefMut, Range};
+use std::ptr::{self, NonNull};
+use std::slice;
+
+/// Size of a CPU cache line.
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// Size of a huge page.
+const HUGE_PAGE_SIZE: usize = 1 << 21;
+
+/// A zero-initialized, owned memory buffer whose start address is aligned to
+/// the CPU cache line size, optionally backed by huge pages.
+///
+/// The buffer is meant to be registered as a memory region with
+/// [`Mr::reg()`](crate::rdma::mr::Mr::reg). Use
+/// [`AlignedBuf::new_isolated()`] to lay out several regions so that no two of
+/// them share a cache line, e.g., to keep flags polled by the CPU away from
+/// bulk data written by the NIC and avoid false sharing.
+///
+/// With the `zeroize` feature, the buffer is zeroized before it is freed.
+pub struct AlignedBuf {
+    /// Start address of the buffer.
+    ptr: NonNull<u8>,
+
+    /// Length of the buffer.
+    len: usize,
+
+    /// Whether the buffer is mapped with huge pages.
+    huge: bool,
+
+    /// Whether the buffer is mapped with `mmap` rather than allocated from
+    /// the global allocator.
+    mapped: bool,
+}
+
+// SAFETY: the buffer is exclusively owned.
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// Allocate a cache-line-aligned buffer with the given length.
+    pub fn new(len: usize) -> io::Result<Self> {
+        if len == 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "zero-length buffers are disallowed",
+            ));
+        }
+
+        let layout = Layout::from_size_align(len, CACHE_LINE_SIZE)
+            .map_err(|e| IoError::new(IoErrorKind::InvalidInput, e))?;
+        // SAFETY: the layout has non-zero size.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr)
+            .ok_or_else(|| IoError::new(IoErrorKind::OutOfMemory, "cannot allocate buffer"))?;
+        Ok(Self {
+            ptr,
+            len,
+            huge: false,
+            mapped: false,
+        })
+    }
+
+    /// Allocate a buffer backed by 2MB huge pages, with the given length
+    /// rounded up to a multiple of the huge page size.
+    ///
+    /// Huge pages reduce the NIC's address translation misses on large
+    /// buffers. This fails if the system has no free huge pages; fall back to
+    /// [`AlignedBuf::new()`] in that case if you wish.
+    pub fn new_huge(len: usize) -> io::Result<Self> {
+        if len == 0 {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "zero-length buffers are disallowed",
+            ));
+        }
+
+        let len = len.next_multiple_of(HUGE_PAGE_SIZE);
+        // SAFETY: FFI.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(IoError::last_os_error());
+        }
+        Ok(Self {
+            // SAFETY: `mmap` never returns null on success.
+            ptr: unsafe { NonNull::new_unchecked(ptr.cast()) },
+            len,
+            huge: true,
+            mapped: true,
+        })
+    }
+
+    /// Take ownership of an anonymous memory mapping of regular pages, which
+    /// is unmapped on drop.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be the start of a readable and writable mapping of exactly
+    /// `len` bytes created by `mmap`, not owned by anything else.
+    pub(super) unsafe fn from_mapping(ptr: NonNull<u8>, len: usize) -> Self {
+        Self {
+            ptr,
+            len,
+            huge: false,
+            mapped: true,
+        }
+    }
+
+    /// Allocate a cache-line-aligned buffer holding regions of the given
+    /// lengths, each starting at a cache line boundary and padded to whole
+    /// cache lines, so that no two regions share a cache line. Return the
+    /// buffer and the byte ranges of the regions within it.
+    pub fn new_isolated(lens: &[usize]) -> io::Result<(Self, Vec<Range<usize>>)> {
+        let (ranges, total) = Self::isolated_layout(lens);
+        Ok((Self::new(total)?, ranges))
+    }
+
+    /// Compute the layout used by [`AlignedBuf::new_isolated()`]. Return the
+    /// byte ranges of the regions, and the total length.
+    pub fn isolated_layout(lens: &[usize]) -> (Vec<Range<usize>>, usize) {
+        let mut offset = 0;
+        let ranges = lens
+            .iter()
+            .map(|&len| {
+                let range = offset..offset + len;
+                offset += len.max(1).next_multiple_of(CACHE_LINE_SIZE);
+                range
+            })
+            .collect();
+        (ranges, offset)
+    }
+
+    /// Get the address of the buffer.
+    #[inline]
+    pub fn addr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Get the length of the buffer.
+    #[inline]
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return `true` if the buffer is backed by huge pages.
+    #[inline]
+    pub fn is_huge(&self) -> bool {
+        self.huge
+    }
+
+    /// Get the address of the cache line of the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the cache line is out of bounds.
+    #[inline]
+    pub fn cache_line(&self, index: usize) -> *mut u8 {
+        assert!(
+            (index + 1) * CACHE_LINE_SIZE <= self.len,
+            "cache line {} out of bounds",
+            index
+        );
+        // SAFETY: in bounds.
+        unsafe { self.addr().add(index * CACHE_LINE_SIZE) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut **self);
+
+        if self.mapped {
+            // SAFETY: the buffer was mapped by `mmap` with the same length.
+            let ret = unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+            assert_eq!(ret, 0, "cannot unmap buffer on drop");
+        } else {
+            // SAFETY: the buffer was allocated with the same layout.
+            unsafe {
+                let layout = Layout::from_size_align_unchecked(self.len, CACHE_LINE_SIZE);
+                alloc::dealloc(self.ptr.as_ptr(), layout);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for AlignedBuf {}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the buffer is valid and initialized.
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the buffer is valid, initialized, and exclusively borrowed.
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
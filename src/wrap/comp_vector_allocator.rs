@@ -0,0 +1,94 @@
+use crate::rdma::context::Context;
+use crate::rdma::cq::*;
+
+/// Spreads the CQs of a context across its completion vectors.
+///
+/// Completion vectors are typically backed by distinct interrupt lines, so
+/// creating many notifiable CQs on the default vector funnels all their
+/// events through a single CPU. The allocator hands out vectors round-robin
+/// and counts the CQs created on each, so that applications with many CQs
+/// get the interrupt load distributed without picking vectors manually.
+///
+/// **NOTE:** only CQs created through the allocator are counted, and CQs are
+/// not uncounted when dropped unless [`CompVectorAllocator::release()`] is
+/// called.
+#[derive(Debug, Clone)]
+pub struct CompVectorAllocator {
+    /// The context to create CQs on.
+    ctx: Context,
+
+    /// Number of CQs created on each vector.
+    usage: Vec<usize>,
+
+    /// The vector to hand out next.
+    next: usize,
+}
+
+impl CompVectorAllocator {
+    /// Create an allocator over all completion vectors of the given context.
+    pub fn new(ctx: &Context) -> Self {
+        // Some providers report no completion vectors, but accept vector 0.
+        let num = ctx.num_comp_vectors().max(1) as usize;
+        Self {
+            ctx: ctx.clone(),
+            usage: vec![0; num],
+            next: 0,
+        }
+    }
+
+    /// Get the number of completion vectors being allocated.
+    #[inline]
+    pub fn num_vectors(&self) -> u32 {
+        self.usage.len() as u32
+    }
+
+    /// Get the number of CQs created on each vector, indexed by vector.
+    #[inline]
+    pub fn usage(&self) -> &[usize] {
+        &self.usage
+    }
+
+    /// Pick the next vector in round-robin order and count one CQ on it.
+    pub fn next_vector(&mut self) -> u32 {
+        let vector = self.next;
+        self.next = (self.next + 1) % self.usage.len();
+        self.usage[vector] += 1;
+        vector as u32
+    }
+
+    /// Build a CQ with the given builder on the next vector in round-robin
+    /// order, overriding the vector set in the builder.
+    ///
+    /// The vector is not counted if the creation fails.
+    pub fn build(&mut self, builder: CqBuilder) -> Result<Cq, CqCreationError> {
+        let vector = self.next_vector();
+        let cq = builder.comp_vector(vector).build(&self.ctx);
+        if cq.is_err() {
+            self.usage[vector as usize] -= 1;
+        }
+        cq
+    }
+
+    /// Create a notifiable CQ of the given capacity on the next vector in
+    /// round-robin order. See [`Cq::new_notifiable()`] for details.
+    pub fn new_notifiable(&mut self, capacity: u32) -> Result<Cq, CqCreationError> {
+        self.build(CqBuilder::new().capacity(capacity).notifiable(true))
+    }
+
+    /// Stop counting a CQ on its vector, e.g., before dropping it.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the CQ belongs to another context, or no CQ is counted on
+    /// its vector.
+    pub fn release(&mut self, cq: &Cq) {
+        assert_eq!(
+            cq.context().as_raw(),
+            self.ctx.as_raw(),
+            "CQ belongs to another context"
+        );
+        let usage = &mut self.usage[cq.comp_vector() as usize];
+        assert!(*usage > 0, "no CQ counted on vector {}", cq.comp_vector());
+        *usage -= 1;
+    }
+}
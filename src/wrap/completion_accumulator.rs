@@ -0,0 +1,97 @@
+use crate::rdma::cq::{Wc, WcStatus};
+use crate::rdma::type_alias::WrId;
+
+/// Translates the completions of periodically signaled send work requests
+/// into per-operation completions.
+///
+/// Pipelines commonly signal only every `period`-th work request to save
+/// completion processing, relying on the fact that a successful signaled
+/// completion implies that all work requests posted before it on the same
+/// send queue have completed as well. The accumulator recovers the
+/// per-operation view: work requests are numbered by consecutive work request
+/// IDs starting from a base, and every signaled completion is expanded into
+/// one logical completion for each ID up to and including its own.
+///
+/// The producer side decides which work requests to signal with
+/// [`CompletionAccumulator::should_signal()`], and the consumer side feeds
+/// the polled completions to [`CompletionAccumulator::accept()`].
+///
+/// **NOTE:** all work requests of the send queue must be numbered this way,
+/// and their completions must be fed in polling order.
+#[derive(Debug, Clone)]
+pub struct CompletionAccumulator {
+    /// Signaling period.
+    period: u64,
+
+    /// Work request ID of the first operation.
+    base: WrId,
+
+    /// Work request ID of the oldest operation not yet completed.
+    next: WrId,
+}
+
+impl CompletionAccumulator {
+    /// Create an accumulator for operations numbered from `base`, of which
+    /// every `period`-th is signaled.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `period` is zero.
+    pub fn new(period: u64, base: WrId) -> Self {
+        assert!(period > 0, "signaling period must be non-zero");
+        Self {
+            period,
+            base,
+            next: base,
+        }
+    }
+
+    /// Get the signaling period.
+    #[inline]
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    /// Get the work request ID of the oldest operation not yet completed.
+    #[inline]
+    pub fn next_wr_id(&self) -> WrId {
+        self.next
+    }
+
+    /// Return `true` if the operation with the given work request ID should
+    /// be signaled, i.e., it is the last of its period.
+    ///
+    /// The last operation of a pipeline should be signaled regardless, so
+    /// that the operations before it are not left pending.
+    #[inline]
+    pub fn should_signal(&self, wr_id: WrId) -> bool {
+        (wr_id.wrapping_sub(self.base) + 1) % self.period == 0
+    }
+
+    /// Expand a completion into per-operation completions, calling `f` with
+    /// the work request ID and status of each operation in posting order.
+    /// Return the number of operations completed.
+    ///
+    /// All operations before the one of the completion are reported
+    /// successful, and that one is reported with the status of the
+    /// completion. This also holds for erroneous completions, which are
+    /// generated for the failing work request even if it is unsignaled.
+    /// Completions of operations that have already been reported, e.g.,
+    /// flushed unsignaled ones after an error, are ignored.
+    pub fn accept(&mut self, wc: &Wc, mut f: impl FnMut(WrId, WcStatus)) -> usize {
+        let wr_id = wc.wr_id();
+        if wr_id.wrapping_sub(self.base) < self.next.wrapping_sub(self.base) {
+            return 0;
+        }
+
+        let mut n = 0;
+        while self.next != wr_id {
+            f(self.next, WcStatus::Success);
+            self.next = self.next.wrapping_add(1);
+            n += 1;
+        }
+        f(wr_id, wc.status());
+        self.next = wr_id.wrapping_add(1);
+        n + 1
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+
+use crate::ctrl::Connecter;
+use crate::rdma::context::{AsyncEvent, Context};
+use crate::rdma::qp::*;
+use crate::rdma::type_alias::{GidIndex, PortNum};
+
+/// Callback invoked on a QP after its connection is rebuilt.
+type RebuildHook = Box<dyn FnMut(&Qp) -> io::Result<()> + Send>;
+
+/// A connection under supervision.
+struct Supervised {
+    /// The RC QP.
+    qp: Qp,
+
+    /// The control channel with the remote peer.
+    connecter: Connecter,
+
+    /// Number of the local port and GID index that the QP is bound to.
+    port_num: PortNum,
+    gid_index: GidIndex,
+
+    /// Callback invoked after the connection is rebuilt.
+    on_rebuild: Option<RebuildHook>,
+}
+
+/// Watches the asynchronous events of a device, and rebuilds the RC
+/// connections whose QPs hit fatal errors.
+///
+/// A QP that reports [`AsyncEvent::QpFatal`] is in the error state: all its
+/// outstanding work requests are flushed, and it cannot be used anymore. The
+/// supervisor keeps the [`Connecter`] channel of every supervised connection,
+/// so that it can reset the QP, bind it to the same local port again, and
+/// exchange fresh endpoints with the remote peer, bringing the connection
+/// back up with the same QP.
+///
+/// Rebuilding a connection needs the cooperation of the remote peer, which
+/// must reconnect its own end through the same `Connecter` channel, e.g.,
+/// with its own supervisor. The remote end of a broken connection usually
+/// does not get an asynchronous event, but sees its work requests fail; it
+/// can then call [`ConnectionSupervisor::rebuild()`] directly.
+///
+/// **NOTE:** the supervisor consumes all asynchronous events of the device,
+/// acknowledging and discarding those unrelated to supervised QPs.
+pub struct ConnectionSupervisor {
+    /// The device context whose events are watched.
+    ctx: Context,
+
+    /// Supervised connections, indexed by QP number.
+    conns: HashMap<u32, Supervised>,
+}
+
+impl ConnectionSupervisor {
+    /// Create a supervisor of connections on the given device.
+    pub fn new(ctx: &Context) -> Self {
+        Self {
+            ctx: ctx.clone(),
+            conns: HashMap::new(),
+        }
+    }
+
+    /// Connect an RC QP with the remote peer through the given `Connecter`,
+    /// and put the connection under supervision. The QP must be already bound
+    /// to a local port of the supervised device. Return the QP number, which
+    /// identifies the connection.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the QP is not RC.
+    /// - Panic if the QP is not bound to a local port.
+    pub fn supervise(&mut self, mut qp: Qp, connecter: Connecter) -> io::Result<u32> {
+        assert_eq!(qp.qp_type(), QpType::Rc, "QP is not RC");
+        let (port, gid_index) = qp.port().expect("QP is not bound to a local port");
+        let (port_num, gid_index) = (port.num(), *gid_index);
+
+        connecter.connect(&mut qp)?;
+        let qpn = qp.qp_num();
+        self.conns.insert(
+            qpn,
+            Supervised {
+                qp,
+                connecter,
+                port_num,
+                gid_index,
+                on_rebuild: None,
+            },
+        );
+        Ok(qpn)
+    }
+
+    /// Set the callback to invoke on the QP of the given connection after it
+    /// is rebuilt, typically to post receive requests again. An error returned
+    /// by the callback is returned from the rebuild.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the connection is not supervised.
+    pub fn on_rebuild(
+        &mut self,
+        qpn: u32,
+        on_rebuild: impl FnMut(&Qp) -> io::Result<()> + Send + 'static,
+    ) {
+        let conn = self
+            .conns
+            .get_mut(&qpn)
+            .expect("connection is not supervised");
+        conn.on_rebuild = Some(Box::new(on_rebuild));
+    }
+
+    /// Stop supervising the given connection, and return its QP and control
+    /// channel. Return `None` if the connection is not supervised.
+    pub fn unsupervise(&mut self, qpn: u32) -> Option<(Qp, Connecter)> {
+        self.conns
+            .remove(&qpn)
+            .map(|conn| (conn.qp, conn.connecter))
+    }
+
+    /// Get the QP of the given connection, or `None` if the connection is not
+    /// supervised.
+    #[inline]
+    pub fn qp(&self, qpn: u32) -> Option<&Qp> {
+        self.conns.get(&qpn).map(|conn| &conn.qp)
+    }
+
+    /// Get the number of supervised connections.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.conns.len()
+    }
+
+    /// Return `true` if no connection is supervised.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.conns.is_empty()
+    }
+
+    /// Tear down and rebuild the given connection: reset its QP, bind it to
+    /// the same local port again, reconnect it with the remote peer, and
+    /// invoke the callback set by [`ConnectionSupervisor::on_rebuild()`].
+    /// Block until the remote peer reconnects its end.
+    ///
+    /// Completions of the flushed work requests are not polled; they remain
+    /// in the CQs of the QP until the application polls them.
+    ///
+    /// Fail with `NotFound` if the connection is not supervised.
+    pub fn rebuild(&mut self, qpn: u32) -> io::Result<()> {
+        let conn = self.conns.get_mut(&qpn).ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::NotFound,
+                format!("QP {} is not supervised", qpn),
+            )
+        })?;
+
+        log::warn!("rebuilding the connection of QP {}", qpn);
+        conn.qp.reset()?;
+
+        // Query the port again, as the snapshot taken at binding time may
+        // report a stale state.
+        let port = self.ctx.query_port(conn.port_num).map_err(IoError::other)?;
+        conn.qp.bind_local_port(&port, Some(conn.gid_index))?;
+        conn.connecter.connect(&mut conn.qp)?;
+
+        if let Some(on_rebuild) = conn.on_rebuild.as_mut() {
+            on_rebuild(&conn.qp)?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the next asynchronous event of the device, and handle it.
+    /// Return the QP number of the connection that was rebuilt, or `None` if
+    /// the event did not concern a supervised connection.
+    ///
+    /// Like [`Context::async_events()`], this method blocks unless the file
+    /// descriptor of the context is set to non-blocking mode, in which case
+    /// it fails with `WouldBlock` when there are no pending events.
+    pub fn handle_next_event(&mut self) -> io::Result<Option<u32>> {
+        let event = match self.ctx.async_events().next() {
+            Some(event) => event?,
+            None => return Ok(None),
+        };
+
+        match event {
+            AsyncEvent::QpFatal(qpn) if self.conns.contains_key(&qpn) => {
+                self.rebuild(qpn)?;
+                Ok(Some(qpn))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Handle the asynchronous events of the device in a loop, rebuilding
+    /// supervised connections as their QPs fail. Only return on error.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.handle_next_event()?;
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use crate::rdma::cq::{Cq, Wc};
+use crate::rdma::qp::Qp;
+
+/// Work queues of a QP that report completions to the demultiplexed CQ.
+#[derive(Debug, Clone, Copy)]
+struct Feeds {
+    send: bool,
+    recv: bool,
+}
+
+/// Classifies the completions of a CQ shared by multiple QPs by the QP and
+/// the work queue that they belong to.
+///
+/// Telling send completions from receive completions by their opcode is
+/// unreliable: the opcode of erroneous completions is undefined. The
+/// demultiplexer instead records, for every QP registered with
+/// [`CqDemux::register()`], whether its send queue, its receive queue, or
+/// both report to the CQ. Completions of a QP with only one of its queues on
+/// the CQ are thus classified regardless of their status; the opcode is only
+/// consulted for QPs with both queues on the CQ.
+#[derive(Debug)]
+pub struct CqDemux {
+    /// The shared CQ.
+    cq: Cq,
+
+    /// Registered QPs, indexed by QP number.
+    qps: HashMap<u32, Feeds>,
+}
+
+impl CqDemux {
+    /// Create a demultiplexer of the given CQ, with no QPs registered.
+    pub fn new(cq: &Cq) -> Self {
+        Self {
+            cq: cq.clone(),
+            qps: HashMap::new(),
+        }
+    }
+
+    /// Get the demultiplexed CQ.
+    #[inline]
+    pub fn cq(&self) -> &Cq {
+        &self.cq
+    }
+
+    /// Register a QP that reports completions of its send queue, its receive
+    /// queue, or both, to the CQ.
+    ///
+    /// # Panics
+    ///
+    /// Panic if neither queue of the QP reports to the CQ.
+    pub fn register(&mut self, qp: &Qp) {
+        let feeds = Feeds {
+            send: qp.scq().as_raw() == self.cq.as_raw(),
+            recv: qp.rcq().as_raw() == self.cq.as_raw(),
+        };
+        assert!(
+            feeds.send || feeds.recv,
+            "neither queue of QP {} reports to this CQ",
+            qp.qp_num()
+        );
+        self.qps.insert(qp.qp_num(), feeds);
+    }
+
+    /// Unregister a QP by its number. Return `false` if it was not registered.
+    pub fn unregister(&mut self, qp_num: u32) -> bool {
+        self.qps.remove(&qp_num).is_some()
+    }
+
+    /// Get the number of registered QPs.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.qps.len()
+    }
+
+    /// Return `true` if no QP is registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.qps.is_empty()
+    }
+
+    /// Classify a completion polled from the CQ, returning the number of the
+    /// QP it belongs to and whether it is a send completion.
+    ///
+    /// Return `None` if the QP is not registered, or if the completion is
+    /// erroneous and both queues of the QP report to the CQ, in which case it
+    /// cannot be told apart. Such completions are usually flushed ones, after
+    /// which the QP must be reset anyway.
+    pub fn classify(&self, wc: &Wc) -> Option<(u32, bool)> {
+        let qp_num = wc.qp_num();
+        let feeds = self.qps.get(&qp_num)?;
+        let is_send = match (feeds.send, feeds.recv) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => {
+                wc.ok().ok()?;
+                !wc.is_recv()
+            }
+        };
+        Some((qp_num, is_send))
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+
+use crate::rdma::cq::{Wc, WcOpcode};
+use crate::rdma::mr::*;
+use crate::rdma::type_alias::ImmData;
+
+/// Maps the immediate data of incoming RDMA writes with immediate to the
+/// local buffers they target.
+///
+/// The receive completion of an RDMA write with immediate only carries the
+/// immediate data, not where the data landed, since the write went straight
+/// to memory by the rkey. Producers and consumers therefore pre-agree on an
+/// immediate value per target buffer: the consumer registers each buffer
+/// here under its value, and [`ImmNotifier::resolve()`] tells which buffer was
+/// just written upon a completion.
+///
+/// The notifier does not post receive work requests; the consumer is still
+/// responsible for keeping the receive queue replenished, as every write with
+/// immediate consumes one.
+#[derive(Debug, Clone, Default)]
+pub struct ImmNotifier<'a> {
+    /// Registered target buffers, by immediate data.
+    targets: HashMap<ImmData, MrSlice<'a>>,
+}
+
+impl<'a> ImmNotifier<'a> {
+    /// Create a notifier with no registered targets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a target buffer under the given immediate data, returning the
+    /// previously registered one, if any.
+    pub fn register(&mut self, imm: ImmData, target: MrSlice<'a>) -> Option<MrSlice<'a>> {
+        self.targets.insert(imm, target)
+    }
+
+    /// Unregister the target buffer under the given immediate data, returning
+    /// it if it was registered.
+    pub fn unregister(&mut self, imm: ImmData) -> Option<MrSlice<'a>> {
+        self.targets.remove(&imm)
+    }
+
+    /// Get the target buffer registered under the given immediate data.
+    #[inline]
+    pub fn target(&self, imm: ImmData) -> Option<&MrSlice<'a>> {
+        self.targets.get(&imm)
+    }
+
+    /// Get the number of registered targets.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Return `true` if no target is registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Resolve a receive completion into its immediate data and the target
+    /// buffer just written.
+    ///
+    /// Return `None` if the completion is not of an RDMA write with
+    /// immediate, e.g., a send, so that callers can pass through all
+    /// completions of a shared receive CQ. Fail with the completion status if
+    /// the completion is erroneous, and with `NotFound` if no target is
+    /// registered under its immediate data.
+    pub fn resolve(&self, wc: &Wc) -> io::Result<Option<(ImmData, &MrSlice<'a>)>> {
+        wc.ok().map_err(IoError::from)?;
+        if wc.opcode() != WcOpcode::RecvRdmaImm {
+            return Ok(None);
+        }
+
+        let imm = wc.imm_unchecked();
+        match self.targets.get(&imm) {
+            Some(target) => Ok(Some((imm, target))),
+            None => Err(IoError::new(
+                IoErrorKind::NotFound,
+                format!("no target registered for immediate data {:#x}", imm),
+            )),
+        }
+    }
+}
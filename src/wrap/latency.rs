@@ -0,0 +1,94 @@
+//! Latency measurement of one-sided RDMA operations.
+
+use std::io::{self, Error as IoError};
+use std::time::{Duration, Instant};
+
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+
+/// Number of untimed operations issued before measuring, to warm up caches
+/// and the NIC's address translation.
+pub const WARMUP_ROUNDS: usize = 128;
+
+/// Latency statistics of a series of operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Number of samples.
+    pub samples: usize,
+
+    /// Minimum latency.
+    pub min: Duration,
+
+    /// Median latency.
+    pub median: Duration,
+
+    /// 99th-percentile latency.
+    pub p99: Duration,
+
+    /// Maximum latency.
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    /// Compute the statistics of the given samples, which are sorted in
+    /// place. Return `None` if there are no samples.
+    pub fn from_samples(samples: &mut [Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        samples.sort_unstable();
+        let n = samples.len();
+        // Nearest-rank percentile.
+        let percentile = |p: usize| samples[(n * p).div_ceil(100).max(1) - 1];
+        Some(Self {
+            samples: n,
+            min: samples[0],
+            median: percentile(50),
+            p99: percentile(99),
+            max: samples[n - 1],
+        })
+    }
+}
+
+/// Measure the round-trip latency of signaled RDMA writes from `local` to
+/// `remote`, i.e., the time from posting a write to polling its completion.
+///
+/// [`WARMUP_ROUNDS`] writes are issued first and not measured. Then `samples`
+/// writes are issued one at a time, each waited for with
+/// [`Cq::poll_one_blocking()`](crate::rdma::cq::Cq::poll_one_blocking).
+/// Fail if any write fails.
+///
+/// **NOTE:** the send CQ of the QP must not be shared with other QPs, and
+/// must have no pending completions.
+///
+/// # Panics
+///
+/// - Panic if `samples` is zero.
+/// - Panic if the QP is neither RC nor UC.
+pub fn measure_write(
+    qp: &Qp,
+    local: &[MrSlice],
+    remote: &MrRemote,
+    samples: usize,
+) -> io::Result<LatencyStats> {
+    assert!(samples > 0, "sample count must be non-zero");
+
+    let write_once = |round: usize| -> io::Result<Duration> {
+        let start = Instant::now();
+        qp.write(local, remote, round as _, None, true)?;
+        let wc = qp.scq().poll_one_blocking()?;
+        let elapsed = start.elapsed();
+        qp.account_send(&wc);
+        wc.ok().map_err(IoError::from)?;
+        Ok(elapsed)
+    };
+
+    for round in 0..WARMUP_ROUNDS {
+        write_once(round)?;
+    }
+    let mut latencies = (0..samples)
+        .map(write_once)
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(LatencyStats::from_samples(&mut latencies).unwrap())
+}
@@ -0,0 +1,161 @@
+use std::io::{self, Error as IoError};
+use std::slice;
+
+use super::RegisteredMem;
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+use crate::rdma::type_alias::ImmData;
+
+/// Immediate data of the last chunk of a message.
+const CHUNK_LAST: ImmData = 0;
+
+/// Immediate data of a chunk followed by more chunks of the same message.
+const CHUNK_MORE: ImmData = 1;
+
+/// A reliable, message-oriented channel on top of RC send/recv.
+///
+/// The channel keeps a ring of receive buffers ("slots") posted to the QP at
+/// all times. Messages larger than one slot are split into chunks, each sent
+/// with an immediate telling whether more chunks follow, and reassembled on
+/// the receiving side. Consumed slots are re-posted automatically.
+///
+/// Both ends must use the same slot size. The number of slots bounds how many
+/// chunks the sender can have in flight before the receiver calls
+/// [`MessageChannel::recv_msg()`]; beyond that, the sender is throttled by
+/// RNR retries, and may fail with `RnrRetryExcErr` if the receiver does not
+/// catch up in time.
+///
+/// **NOTE:** the channel polls the send and receive CQs of the QP, which must
+/// therefore be distinct and not shared with other QPs.
+pub struct MessageChannel {
+    /// The connected RC QP.
+    qp: Qp,
+
+    /// Staging buffer of outgoing chunks, one slot long.
+    send_buf: RegisteredMem,
+
+    /// Receive buffers, `slots * slot_size` long.
+    recv_bufs: RegisteredMem,
+
+    /// Size of one slot.
+    slot_size: usize,
+}
+
+impl MessageChannel {
+    /// The default slot size.
+    pub const DEFAULT_SLOT_SIZE: usize = 4096;
+
+    /// Create a message channel on a connected RC QP, with the given number of
+    /// receive slots of the given size. All slots are posted immediately.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the QP is not RC.
+    /// - Panic if the QP's send and receive CQs are the same.
+    /// - Panic if `slots` or `slot_size` is zero, or if `slots` exceeds the
+    ///   receive queue depth of the QP.
+    pub fn new(qp: Qp, slots: usize, slot_size: usize) -> io::Result<Self> {
+        assert_eq!(qp.qp_type(), QpType::Rc, "QP is not RC");
+        assert_ne!(
+            qp.scq().as_raw(),
+            qp.rcq().as_raw(),
+            "send and receive CQs must be distinct"
+        );
+        assert!(
+            slots > 0 && slot_size > 0,
+            "slot count and size must be non-zero"
+        );
+        assert!(
+            slots <= qp.caps().max_recv_wr as usize,
+            "{} slots exceed the receive queue depth {}",
+            slots,
+            qp.caps().max_recv_wr
+        );
+
+        let send_buf = RegisteredMem::new(qp.pd(), slot_size)?;
+        let recv_bufs = RegisteredMem::new(qp.pd(), slots * slot_size)?;
+        let this = Self {
+            qp,
+            send_buf,
+            recv_bufs,
+            slot_size,
+        };
+        for slot in 0..slots {
+            this.post_slot(slot)?;
+        }
+        Ok(this)
+    }
+
+    /// Get the underlying QP.
+    #[inline]
+    pub fn qp(&self) -> &Qp {
+        &self.qp
+    }
+
+    /// Get the slot size.
+    #[inline]
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    /// Send a message, blocking until all of its chunks are sent.
+    pub fn send_msg(&mut self, msg: &[u8]) -> io::Result<()> {
+        // An empty message is still sent as one empty chunk.
+        if msg.is_empty() {
+            return self.send_chunk(msg, false);
+        }
+
+        let mut chunks = msg.chunks(self.slot_size).peekable();
+        while let Some(chunk) = chunks.next() {
+            self.send_chunk(chunk, chunks.peek().is_some())?;
+        }
+        Ok(())
+    }
+
+    /// Receive a message, blocking until all of its chunks have arrived.
+    pub fn recv_msg(&mut self) -> io::Result<Vec<u8>> {
+        let mut msg = Vec::new();
+        loop {
+            let wc = self.qp.rcq().poll_one_blocking()?;
+            self.qp.account_recv(&wc);
+            let len = wc.ok().map_err(IoError::from)?;
+
+            let start = wc.wr_id() as usize * self.slot_size;
+            msg.extend_from_slice(&self.recv_bufs[start..start + len]);
+            self.post_slot(wc.wr_id() as usize)?;
+
+            if wc.imm() != Some(CHUNK_MORE) {
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Post the receive buffer of the given slot, using the slot index as the
+    /// work request ID.
+    fn post_slot(&self, slot: usize) -> io::Result<()> {
+        let buf = self
+            .recv_bufs
+            .slice(slot * self.slot_size, self.slot_size)
+            .unwrap();
+        self.qp.recv(&[buf], slot as _)
+    }
+
+    /// Send one chunk of at most one slot long and wait for its completion.
+    fn send_chunk(&mut self, chunk: &[u8], more: bool) -> io::Result<()> {
+        self.send_buf[..chunk.len()].copy_from_slice(chunk);
+
+        let imm = if more { CHUNK_MORE } else { CHUNK_LAST };
+        let buf = self.send_buf.slice(0, chunk.len()).unwrap();
+        let local = if chunk.is_empty() {
+            &[][..]
+        } else {
+            slice::from_ref(&buf)
+        };
+        self.qp.send(local, None, Some(imm), 0, true, false)?;
+
+        let wc = self.qp.scq().poll_one_blocking()?;
+        self.qp.account_send(&wc);
+        wc.ok().map_err(IoError::from)?;
+        Ok(())
+    }
+}
@@ -1,4 +1,43 @@
 //! Higher-level wrappings of RDMA resources.
 
+mod aligned_buf;
+mod comp_vector_allocator;
+mod completion_accumulator;
+mod connection_supervisor;
+mod cq_demux;
+mod imm_notifier;
+pub mod latency;
+mod message_channel;
+#[cfg(mlnx5)]
+mod mw_pool;
+pub mod numa;
+mod rc_connection;
+mod rdma_barrier;
+mod recv_tracker;
 mod registered_mem;
+mod remote_atomics;
+mod remote_mutex;
+mod remote_ring;
+mod replicator;
+mod responder;
+mod scatter_gather;
+
+pub use aligned_buf::{AlignedBuf, CACHE_LINE_SIZE};
+pub use comp_vector_allocator::CompVectorAllocator;
+pub use completion_accumulator::CompletionAccumulator;
+pub use connection_supervisor::ConnectionSupervisor;
+pub use cq_demux::CqDemux;
+pub use imm_notifier::ImmNotifier;
+pub use message_channel::MessageChannel;
+#[cfg(mlnx5)]
+pub use mw_pool::MwPool;
+pub use rc_connection::RcConnection;
+pub use rdma_barrier::RdmaBarrier;
+pub use recv_tracker::RecvTracker;
 pub use registered_mem::RegisteredMem;
+pub use remote_atomics::RemoteAtomics;
+pub use remote_mutex::RemoteMutex;
+pub use remote_ring::RemoteRing;
+pub use replicator::Replicator;
+pub use responder::Responder;
+pub use scatter_gather::ScatterGather;
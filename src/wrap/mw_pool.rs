@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::ops::RangeBounds;
+
+use crate::rdma::cq::Wc;
+use crate::rdma::mr::*;
+use crate::rdma::pd::Pd;
+use crate::rdma::qp::Qp;
+use crate::rdma::type_alias::{RKey, WrId};
+
+/// A window whose invalidation has been posted but not yet completed.
+struct Revoking {
+    /// Number of the QP that the invalidation was posted to.
+    qp_num: u32,
+
+    /// Work request ID of the invalidation.
+    wr_id: WrId,
+
+    /// Remote key of the revoked handle.
+    rkey: RKey,
+
+    /// The window.
+    mw: Mw,
+}
+
+/// A pool of type 2 memory windows on a protection domain, for granting
+/// short-lived, revocable remote access to parts of memory regions.
+///
+/// [`MwPool::grant()`] binds a window from the pool to a range of a memory
+/// region and hands out its remote handle, and [`MwPool::revoke()`] posts a
+/// signaled local invalidate for it. The window only returns to the pool once
+/// the completion of the invalidate is passed to [`MwPool::reclaim()`], as
+/// the handle stays valid until then. Every binding rotates the remote key of
+/// the window, so a revoked handle does not become valid again when the
+/// window is reused for another grant.
+///
+/// Windows are allocated on demand and kept for reuse; they are deallocated
+/// when the pool is dropped.
+///
+/// **NOTE:** the memory regions must be registered with
+/// [`Permission::MW_BIND`]. Grants and revocations of the same window should
+/// go through the same QP, as some devices restrict type 2 windows to the QP
+/// they are bound with.
+pub struct MwPool {
+    /// The protection domain to allocate windows on.
+    pd: Pd,
+
+    /// Unbound windows.
+    free: Vec<Mw>,
+
+    /// Bound windows, by the remote key of their handles.
+    granted: HashMap<RKey, Mw>,
+
+    /// Windows being invalidated.
+    revoking: Vec<Revoking>,
+}
+
+impl MwPool {
+    /// Create a pool with `prealloc` windows allocated upfront.
+    pub fn new(pd: &Pd, prealloc: usize) -> io::Result<Self> {
+        let free = (0..prealloc)
+            .map(|_| Mw::new(pd))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Self {
+            pd: pd.clone(),
+            free,
+            granted: HashMap::new(),
+            revoking: Vec::new(),
+        })
+    }
+
+    /// Get the number of windows currently granted.
+    #[inline]
+    pub fn granted(&self) -> usize {
+        self.granted.len()
+    }
+
+    /// Get the number of windows being revoked, i.e., whose invalidate
+    /// completions have not been reclaimed yet.
+    #[inline]
+    pub fn revoking(&self) -> usize {
+        self.revoking.len()
+    }
+
+    /// Get the number of windows available for granting without allocation.
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Grant remote access with permission `perm` to the given range of a
+    /// memory region, by binding a window from the pool through the given QP.
+    /// Return the remote handle of the window, which can be sent to the QP's
+    /// peer right away. See [`Mr::bind_window()`] for details.
+    pub fn grant(
+        &mut self,
+        qp: &Qp,
+        mr: &Mr,
+        range: impl RangeBounds<usize>,
+        perm: Permission,
+        wr_id: WrId,
+        signal: bool,
+    ) -> io::Result<MrRemote> {
+        let mw = match self.free.pop() {
+            Some(mw) => mw,
+            None => Mw::new(&self.pd)?,
+        };
+        match mr.bind_window(qp, &mw, range, perm, wr_id, signal) {
+            Ok(handle) => {
+                self.granted.insert(handle.rkey, mw);
+                Ok(handle)
+            }
+            Err(e) => {
+                self.free.push(mw);
+                Err(e)
+            }
+        }
+    }
+
+    /// Revoke a handle returned by [`MwPool::grant()`] by posting a signaled
+    /// local invalidate request for its window to the given QP. See
+    /// [`Mw::invalidate()`] for details.
+    ///
+    /// The handle stays valid until the invalidate completes. Pass its
+    /// completion, identified by the QP number and `wr_id`, to
+    /// [`MwPool::reclaim()`] to return the window to the pool.
+    ///
+    /// Fail with `NotFound` if the handle is not currently granted by this
+    /// pool.
+    pub fn revoke(&mut self, qp: &Qp, handle: &MrRemote, wr_id: WrId) -> io::Result<()> {
+        let mw = self.granted.get(&handle.rkey).ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::NotFound,
+                format!("no window granted with rkey {:#x}", handle.rkey),
+            )
+        })?;
+        mw.invalidate(qp, wr_id, true)?;
+
+        let mw = self.granted.remove(&handle.rkey).unwrap();
+        self.revoking.push(Revoking {
+            qp_num: qp.qp_num(),
+            wr_id,
+            rkey: handle.rkey,
+            mw,
+        });
+        Ok(())
+    }
+
+    /// Reclaim the window of a revocation whose invalidate completion is
+    /// `wc`. Return `false` if the completion does not belong to a pending
+    /// revocation.
+    ///
+    /// On success, the window returns to the pool. If the invalidate failed,
+    /// the handle may still be valid, so the window is considered granted
+    /// again and the handle can be revoked anew.
+    pub fn reclaim(&mut self, wc: &Wc) -> bool {
+        let Some(pos) = self
+            .revoking
+            .iter()
+            .position(|r| r.qp_num == wc.qp_num() && r.wr_id == wc.wr_id())
+        else {
+            return false;
+        };
+
+        let revoking = self.revoking.remove(pos);
+        if wc.ok().is_ok() {
+            self.free.push(revoking.mw);
+        } else {
+            log::warn!(
+                "invalidating window with rkey {:#x} failed: {:?}",
+                revoking.rkey,
+                wc.status()
+            );
+            self.granted.insert(revoking.rkey, revoking.mw);
+        }
+        true
+    }
+}
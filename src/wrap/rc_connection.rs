@@ -0,0 +1,96 @@
+use std::io::{self, ErrorKind as IoErrorKind};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ctrl::Connecter;
+use crate::rdma::qp::*;
+
+/// Control messages exchanged between the two ends of an [`RcConnection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ControlMsg {
+    /// The sender has stopped posting work requests and is about to reset
+    /// its QP.
+    Disconnect,
+}
+
+/// An RC connection with a remote peer, together with the [`Connecter`]
+/// channel that established it.
+///
+/// Keeping the control channel around allows the two ends to coordinate the
+/// teardown with [`RcConnection::disconnect()`], so that neither side sees its
+/// peer QP vanish with work requests in flight, which would otherwise end up
+/// in `RetryExcErr` completions.
+pub struct RcConnection {
+    /// The connected RC QP.
+    qp: Qp,
+
+    /// The control channel with the remote peer.
+    connecter: Connecter,
+}
+
+impl RcConnection {
+    /// Connect an RC QP with the remote peer through the given `Connecter`.
+    /// The QP must be already bound to a local port.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the QP is not RC.
+    /// - Panic if the QP is not bound to a local port.
+    pub fn new(mut qp: Qp, connecter: Connecter) -> io::Result<Self> {
+        assert_eq!(qp.qp_type(), QpType::Rc, "QP is not RC");
+        connecter.connect(&mut qp)?;
+        Ok(Self { qp, connecter })
+    }
+
+    /// Get the connected QP.
+    #[inline]
+    pub fn qp(&self) -> &Qp {
+        &self.qp
+    }
+
+    /// Get the control channel with the remote peer.
+    #[inline]
+    pub fn connecter(&self) -> &Connecter {
+        &self.connecter
+    }
+
+    /// Check whether the remote peer is alive, by probing it with
+    /// [`Qp::keepalive()`] and waiting at most `timeout` for the result.
+    ///
+    /// Return `false` if the peer is unreachable or the probe does not
+    /// complete in time, which allows services to reap dead connections
+    /// without waiting for the next send to fail. A connection whose peer is
+    /// unreachable has its QP in the error state and cannot be used anymore.
+    pub fn is_alive(&self, timeout: Duration) -> io::Result<bool> {
+        match self.qp.keepalive(timeout) {
+            Ok(()) => Ok(true),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    IoErrorKind::ConnectionAborted | IoErrorKind::TimedOut
+                ) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Disconnect from the remote peer.
+    ///
+    /// This method tells the remote peer to stop, waits for it to do the same,
+    /// and then modifies the QP to RESET. Both ends are expected to call this
+    /// method, after they have stopped posting work requests and have reaped
+    /// the completions they care about. Return the QP in RESET state, which
+    /// can be bound to a port and connected again.
+    pub fn disconnect(self) -> io::Result<Qp> {
+        let Self { mut qp, connecter } = self;
+
+        connecter.send_value(&ControlMsg::Disconnect)?;
+        let ControlMsg::Disconnect = connecter.recv_value::<ControlMsg>()?;
+
+        qp.reset()?;
+        Ok(qp)
+    }
+}
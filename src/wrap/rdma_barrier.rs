@@ -0,0 +1,157 @@
+use std::io::{self, Error as IoError};
+use std::{hint, mem, ptr};
+
+use super::RegisteredMem;
+use crate::rdma::mr::*;
+use crate::rdma::pd::*;
+use crate::rdma::qp::*;
+
+/// A dissemination barrier that synchronizes nodes purely with one-sided RDMA
+/// writes, keeping TCP out of the synchronization path.
+///
+/// With `n` participating nodes, one barrier takes `ceil(log2(n))` rounds. In
+/// round `k`, the node of rank `r` writes the current epoch into the `k`-th flag
+/// slot of rank `(r + 2^k) mod n`, and then spins on its own `k`-th flag slot
+/// until rank `(r - 2^k) mod n` has done the same to it.
+///
+/// To set up a barrier:
+///
+/// 1. Create it with [`RdmaBarrier::new()`] on every node.
+/// 2. Exchange the flag buffers (see [`RdmaBarrier::flags_remote()`]) and
+///    connect one RC QP to each of the ranks listed by
+///    [`RdmaBarrier::targets()`] by whatever means.
+/// 3. Hand the QPs over with [`RdmaBarrier::connect()`].
+///
+/// **NOTE:** the barrier polls the send CQs of its QPs for write completions.
+/// Those CQs should therefore not be shared with QPs used for other purposes.
+pub struct RdmaBarrier {
+    /// Rank of this node.
+    rank: usize,
+
+    /// Number of participating nodes.
+    nodes: usize,
+
+    /// Flag slots that other nodes write to, one `u64` per round.
+    flags: RegisteredMem,
+
+    /// Source buffer of the outgoing flag writes.
+    src: RegisteredMem,
+
+    /// Connected QPs and remote flag buffers, one per round.
+    targets: Vec<(Qp, MrRemote)>,
+
+    /// Number of barriers passed so far.
+    epoch: u64,
+}
+
+impl RdmaBarrier {
+    /// Create a barrier for the node of the given rank among `nodes` nodes.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `rank` is not smaller than `nodes`.
+    pub fn new(pd: &Pd, rank: usize, nodes: usize) -> io::Result<Self> {
+        assert!(
+            rank < nodes,
+            "rank {} out of range for {} nodes",
+            rank,
+            nodes
+        );
+
+        let rounds = nodes.next_power_of_two().trailing_zeros() as usize;
+        let flags = RegisteredMem::new(pd, rounds.max(1) * mem::size_of::<u64>())?;
+        let src = RegisteredMem::new(pd, mem::size_of::<u64>())?;
+        if flags.addr() as usize % mem::align_of::<u64>() != 0 {
+            return Err(IoError::other("barrier flag buffer is not 8-byte aligned"));
+        }
+
+        Ok(Self {
+            rank,
+            nodes,
+            flags,
+            src,
+            targets: Vec::with_capacity(rounds),
+            epoch: 0,
+        })
+    }
+
+    /// Get the number of rounds one barrier takes.
+    #[inline]
+    pub fn rounds(&self) -> usize {
+        self.nodes.next_power_of_two().trailing_zeros() as usize
+    }
+
+    /// Get the ranks of the nodes this node writes to, in round order.
+    /// The QPs given to [`RdmaBarrier::connect()`] must be connected to these
+    /// nodes in the same order.
+    pub fn targets(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.rounds()).map(move |k| (self.rank + (1 << k)) % self.nodes)
+    }
+
+    /// Get the remote memory handle of the local flag buffer, which should be
+    /// sent to the nodes that write to this node.
+    #[inline]
+    pub fn flags_remote(&self) -> MrRemote {
+        self.flags.mr().as_remote()
+    }
+
+    /// Provide the connected RC QPs and the remote flag buffers for each round.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the number of targets does not equal [`RdmaBarrier::rounds()`].
+    /// - Panic if any remote flag buffer is too small.
+    pub fn connect(&mut self, targets: Vec<(Qp, MrRemote)>) {
+        assert_eq!(
+            targets.len(),
+            self.rounds(),
+            "barrier requires exactly one target per round"
+        );
+        assert!(
+            targets
+                .iter()
+                .all(|(_, remote)| remote.len >= self.rounds() * mem::size_of::<u64>()),
+            "remote barrier flag buffer is too small"
+        );
+        self.targets = targets;
+    }
+
+    /// Wait until all nodes have reached the barrier.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the barrier is not connected yet.
+    pub fn wait(&mut self) -> io::Result<()> {
+        assert_eq!(
+            self.targets.len(),
+            self.rounds(),
+            "barrier is not connected"
+        );
+
+        self.epoch += 1;
+        // SAFETY: the source buffer is exactly a `u64` long.
+        unsafe { ptr::write_unaligned(self.src.addr() as *mut u64, self.epoch) };
+
+        let src = self.src.as_slice();
+        for (k, (qp, remote)) in self.targets.iter().enumerate() {
+            let slot = remote
+                .slice(k * mem::size_of::<u64>(), mem::size_of::<u64>())
+                .unwrap();
+            qp.write(&[src], &slot, k as _, None, true)?;
+            let wc = qp.scq().poll_one_blocking()?;
+            qp.account_send(&wc);
+            if let Err(status) = wc.ok() {
+                return Err(status.into());
+            }
+
+            // A fast neighbor may already have entered the next barrier, so
+            // wait for an epoch no smaller than ours instead of an exact match.
+            // SAFETY: the slot is within the flag buffer and 8-byte aligned.
+            let flag = unsafe { (self.flags.addr() as *const u64).add(k) };
+            while unsafe { ptr::read_volatile(flag) } < self.epoch {
+                hint::spin_loop();
+            }
+        }
+        Ok(())
+    }
+}
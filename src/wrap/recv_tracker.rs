@@ -0,0 +1,157 @@
+use std::fmt;
+use std::io;
+
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+use crate::rdma::srq::Srq;
+use crate::rdma::type_alias::WrId;
+
+/// Receive queue that a [`RecvTracker`] posts to.
+#[derive(Debug, Clone, Copy)]
+enum RecvTarget<'a> {
+    Qp(&'a Qp),
+    Srq(&'a Srq),
+}
+
+/// Tracks the receive buffers posted to a QP or an SRQ and not yet consumed,
+/// so that they can be replenished before the receive queue runs empty.
+///
+/// An empty receive queue makes the remote sender see RNR NAKs, and
+/// eventually `RnrRetryExcErr` completions; the local side has no way to
+/// learn about it from the verbs interface. The tracker counts receive work
+/// requests posted through it against the completions reported back with
+/// [`RecvTracker::consumed()`], and invokes a callback when the number of
+/// available buffers drops below a low watermark.
+///
+/// The tracker of a QP does not keep a count of its own, but builds on
+/// [`Qp::recv_outstanding()`], so that receive work requests posted and
+/// completions accounted elsewhere, e.g., with [`Qp::recv()`] and
+/// [`Qp::account_recv()`], are taken into account as well. SRQs do not track
+/// their receive work requests, so the tracker of an SRQ counts those posted
+/// through it.
+///
+/// **NOTE:** the count of an SRQ is only accurate if all receive work
+/// requests of the queue are posted through the tracker, and all of their
+/// completions are reported to it.
+pub struct RecvTracker<'a> {
+    /// The receive queue.
+    target: RecvTarget<'a>,
+
+    /// Receive buffers posted to the SRQ and not yet consumed. Unused for
+    /// QPs, which count them themselves.
+    available: usize,
+
+    /// Threshold below which the callback is invoked.
+    low_watermark: usize,
+
+    /// Callback invoked with the number of available buffers upon dropping
+    /// below the low watermark.
+    on_low: Option<Box<dyn FnMut(usize) + 'a>>,
+}
+
+impl fmt::Debug for RecvTracker<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecvTracker")
+            .field("target", &self.target)
+            .field("available", &self.recv_buffers_available())
+            .field("low_watermark", &self.low_watermark)
+            .finish()
+    }
+}
+
+impl<'a> RecvTracker<'a> {
+    /// Create a tracker of the receive queue of the given QP, starting from
+    /// its [`Qp::recv_outstanding()`].
+    pub fn for_qp(qp: &'a Qp) -> Self {
+        Self::new(RecvTarget::Qp(qp))
+    }
+
+    /// Create a tracker of the given SRQ. No receive buffers are assumed to be
+    /// posted yet.
+    pub fn for_srq(srq: &'a Srq) -> Self {
+        Self::new(RecvTarget::Srq(srq))
+    }
+
+    fn new(target: RecvTarget<'a>) -> Self {
+        Self {
+            target,
+            available: 0,
+            low_watermark: 0,
+            on_low: None,
+        }
+    }
+
+    /// Set the low watermark and the callback to invoke when the number of
+    /// available receive buffers drops below it. The callback receives the
+    /// number of available buffers, and typically schedules replenishment.
+    ///
+    /// The callback is invoked once per crossing, i.e., it is not invoked
+    /// again until the count has risen to the watermark and dropped below it
+    /// again.
+    pub fn with_low_watermark(
+        mut self,
+        low_watermark: usize,
+        on_low: impl FnMut(usize) + 'a,
+    ) -> Self {
+        self.low_watermark = low_watermark;
+        self.on_low = Some(Box::new(on_low));
+        self
+    }
+
+    /// Get the number of receive buffers posted and not yet consumed.
+    #[inline]
+    pub fn recv_buffers_available(&self) -> usize {
+        match self.target {
+            RecvTarget::Qp(qp) => qp.recv_outstanding() as usize,
+            RecvTarget::Srq(_) => self.available,
+        }
+    }
+
+    /// Return `true` if the number of available receive buffers is below the
+    /// low watermark.
+    #[inline]
+    pub fn is_low(&self) -> bool {
+        self.recv_buffers_available() < self.low_watermark
+    }
+
+    /// Post a receive work request to the tracked queue.
+    pub fn post(&mut self, local: &[MrSlice], wr_id: WrId) -> io::Result<()> {
+        match self.target {
+            RecvTarget::Qp(qp) => qp.recv(local, wr_id)?,
+            RecvTarget::Srq(srq) => {
+                srq.recv(local, wr_id)?;
+                self.available += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Report that `n` posted receive buffers have been consumed, i.e., that
+    /// `n` receive completions, successful or not, have been polled. For a
+    /// QP, they are accounted on the QP itself, so do not also pass them to
+    /// [`Qp::account_recv()`].
+    ///
+    /// # Panics
+    ///
+    /// Panic if more buffers are reported consumed than posted.
+    pub fn consumed(&mut self, n: usize) {
+        let available = self.recv_buffers_available();
+        assert!(
+            n <= available,
+            "{} receive buffers consumed, but only {} posted",
+            n,
+            available
+        );
+
+        let was_low = self.is_low();
+        match self.target {
+            RecvTarget::Qp(qp) => qp.recv_consumed(n as u32),
+            RecvTarget::Srq(_) => self.available -= n,
+        }
+        if !was_low && self.is_low() {
+            if let Some(on_low) = self.on_low.as_mut() {
+                on_low(self.recv_buffers_available());
+            }
+        }
+    }
+}
@@ -7,7 +7,8 @@ use crate::rdma::pd::*;
 
 /// A wrapper around an owned memory area that is registered as an RDMA MR.
 /// The memory area is allocated on the heap with `Box<[u8]>` and will be
-/// deallocated when this structure is dropped. The MR has full permission.
+/// deallocated when this structure is dropped. The MR has the default
+/// permission of the PD handle, see [`Pd::with_default_permission()`].
 ///
 /// **WARNING:** Since Rust disallows self-referencing, this type deceives
 /// the borrow checker by storing a `Mr<'static>` inside. Generally, this
@@ -15,6 +16,9 @@ use crate::rdma::pd::*;
 /// allocated on the heap, which isn't movable, and that it will definitely
 /// get dropped before the referenced memory.
 /// However, you should still use this type with some care.
+///
+/// With the `zeroize` feature, the memory area is zeroized before it is
+/// deallocated.
 pub struct RegisteredMem {
     /// The memory region, dropped first.
     mr: Mr,
@@ -55,7 +59,7 @@ impl RegisteredMem {
         let buf = Box::leak(buf);
 
         // SAFETY: the buffer is valid.
-        let mr = unsafe { Mr::reg(pd, buf.as_mut_ptr(), buf.len(), Permission::default()) };
+        let mr = unsafe { pd.reg(buf.as_mut_ptr(), buf.len()) };
 
         // Pack the leaked buffer back.
         // SAFETY: the buffer is valid and just leaked out from a `Box`.
@@ -111,6 +115,16 @@ impl RegisteredMem {
     }
 }
 
+#[cfg(feature = "zeroize")]
+impl Drop for RegisteredMem {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut *self.buf);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for RegisteredMem {}
+
 impl Deref for RegisteredMem {
     type Target = [u8];
 
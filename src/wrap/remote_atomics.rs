@@ -0,0 +1,104 @@
+use std::io::{self, Error as IoError};
+use std::{hint, ptr};
+
+use super::AlignedBuf;
+use crate::rdma::cq::Wc;
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+use crate::rdma::type_alias::WrId;
+
+/// Work request ID of the atomic operations posted by the wrappers.
+pub(super) const FETCH_WR_ID: WrId = 0xFFFE << 48;
+
+/// Wait for the completion of the atomic operation posted on `qp` with
+/// [`FETCH_WR_ID`], and read the fetched value from the 8-byte scratch
+/// buffer `buf`. Completions of other work requests polled meanwhile are
+/// deferred; see [`Cq::poll_matching()`](crate::rdma::cq::Cq::poll_matching).
+pub(super) fn wait_fetched(qp: &Qp, buf: &AlignedBuf) -> io::Result<u64> {
+    let qp_num = qp.qp_num();
+    let is_fetch = |wc: &Wc| wc.qp_num() == qp_num && wc.wr_id() == FETCH_WR_ID;
+    let mut wc = [Wc::default()];
+    while qp.scq().poll_matching(is_fetch, &mut wc)?.0 == 0 {
+        hint::spin_loop();
+    }
+    qp.account_send(&wc[0]);
+    wc[0].ok().map_err(IoError::from)?;
+
+    // SAFETY: the scratch buffer is 8 bytes long and cache-line aligned, and
+    // the device has finished writing it.
+    Ok(unsafe { ptr::read_volatile(buf.addr() as *const u64) })
+}
+
+/// Blocking RDMA atomics that return the fetched value directly.
+///
+/// The verbs interface reports the original value of an atomic operation by
+/// writing it into a local buffer. This wrapper owns an 8-byte registered
+/// scratch buffer for that purpose, and every operation posts the request,
+/// polls its completion, and reads the fetched value back from the scratch.
+///
+/// The wrapper polls the send CQ of the QP for the completion of each
+/// operation, recognized by its QP number and work request ID; other
+/// completions polled meanwhile are deferred and returned by subsequent polls
+/// of the CQ.
+pub struct RemoteAtomics {
+    /// The connected RC QP.
+    qp: Qp,
+
+    /// The memory region of `buf`, dropped first.
+    mr: Mr,
+
+    /// The scratch buffer.
+    buf: AlignedBuf,
+}
+
+impl RemoteAtomics {
+    /// Create the wrapper on a connected RC QP.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the QP is not RC.
+    pub fn new(qp: Qp) -> io::Result<Self> {
+        assert_eq!(qp.qp_type(), QpType::Rc, "QP is not RC");
+
+        let buf = AlignedBuf::new(8)?;
+        // SAFETY: the buffer outlives the MR, which is dropped first.
+        let mr = unsafe { qp.pd().reg(buf.addr(), buf.len())? };
+        Ok(Self { qp, mr, buf })
+    }
+
+    /// Get the underlying QP.
+    #[inline]
+    pub fn qp(&self) -> &Qp {
+        &self.qp
+    }
+
+    /// Atomically replace the 8 bytes at `remote` with `new` if they equal
+    /// `current`, and return their original value. The swap took place if
+    /// and only if the returned value equals `current`.
+    pub fn atomic_cas(&self, remote: &MrRemote, current: u64, new: u64) -> io::Result<u64> {
+        self.qp
+            .compare_swap_fetch(&self.scratch(), *remote, current, new, FETCH_WR_ID)?;
+        self.fetched()
+    }
+
+    /// Atomically add `add` to the 8 bytes at `remote`, and return their
+    /// original value.
+    pub fn atomic_faa(&self, remote: &MrRemote, add: u64) -> io::Result<u64> {
+        self.qp
+            .fetch_add(self.scratch(), *remote, add, FETCH_WR_ID, true)?;
+        self.fetched()
+    }
+
+    /// Get the scratch buffer as a memory region slice.
+    #[inline]
+    fn scratch(&self) -> MrSlice<'_> {
+        self.mr.slice(0, 8).unwrap()
+    }
+
+    /// Wait for the completion of the atomic operation and read the fetched
+    /// value.
+    #[inline]
+    fn fetched(&self) -> io::Result<u64> {
+        wait_fetched(&self.qp, &self.buf)
+    }
+}
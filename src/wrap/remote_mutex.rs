@@ -0,0 +1,263 @@
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{hint, thread};
+
+use super::remote_atomics::{wait_fetched, FETCH_WR_ID};
+use super::AlignedBuf;
+use crate::rdma::mr::*;
+use crate::rdma::pd::Pd;
+use crate::rdma::qp::*;
+
+/// Number of low bits of a leased lock word that hold the lease deadline.
+const DEADLINE_BITS: u32 = 48;
+
+/// Mask of the lease deadline in a leased lock word.
+const DEADLINE_MASK: u64 = (1 << DEADLINE_BITS) - 1;
+
+/// Number of backoff rounds that spin, with exponentially growing lengths,
+/// before falling back to yielding the thread.
+const SPIN_ROUNDS: u32 = 10;
+
+/// A mutual exclusion lock on an 8-byte word in remote memory, acquired and
+/// released with RDMA compare-and-swap.
+///
+/// The lock word is zero when the lock is free, and holds the non-zero ID of
+/// the holder otherwise. Every participant creates its own `RemoteMutex` on
+/// the same lock word with a distinct ID, and [`RemoteMutex::lock()`] swaps
+/// the word from zero to its ID, backing off between failed attempts;
+/// [`RemoteMutex::unlock()`] swaps it back. The lock word this end has
+/// written is kept locally, so that it knows whether it holds the lock.
+///
+/// # Leases
+///
+/// A holder that crashes leaves the lock held forever. With
+/// [`RemoteMutex::with_lease()`], the lock word instead carries the ID in its
+/// upper 16 bits and a deadline, in milliseconds since the UNIX epoch, in the
+/// lower 48 bits. A lock whose deadline has passed is considered abandoned,
+/// and the next locker takes it over. Holders of long critical sections must
+/// therefore extend their lease with [`RemoteMutex::renew()`]. All
+/// participants must agree on using leases, and their clocks must be
+/// synchronized far more tightly than the lease length.
+///
+/// Operations poll the send CQ of the given QP for their completions like
+/// [`RemoteAtomics`](super::RemoteAtomics) does, deferring other completions.
+/// They take `&mut self`, as they share a scratch buffer and the local record
+/// of the held lock word; threads contending for the lock each need their
+/// own `RemoteMutex` with a distinct ID.
+pub struct RemoteMutex {
+    /// The lock word.
+    remote: MrRemote,
+
+    /// The ID of this end.
+    tid: u64,
+
+    /// The lease length, if leases are used.
+    lease: Option<Duration>,
+
+    /// The lock word written by this end if it holds the lock, or zero.
+    held: u64,
+
+    /// The memory region of `buf`, dropped first.
+    mr: Mr,
+
+    /// Scratch buffer for the original values of the lock word.
+    buf: AlignedBuf,
+}
+
+impl RemoteMutex {
+    /// Create one end of a lock on the first 8 bytes of `remote`, identified
+    /// by `tid`. The scratch buffer is registered on the given PD, which must
+    /// be the PD of the QPs that operate the lock.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if `tid` is zero.
+    /// - Panic if `remote` is shorter than 8 bytes.
+    pub fn new(pd: &Pd, remote: MrRemote, tid: u64) -> io::Result<Self> {
+        assert_ne!(tid, 0, "lock holder ID must be non-zero");
+        assert!(remote.len >= 8, "lock word must be 8 bytes long");
+
+        let buf = AlignedBuf::new(8)?;
+        // SAFETY: the buffer outlives the MR, which is dropped first.
+        let mr = unsafe { pd.reg(buf.addr(), buf.len())? };
+        Ok(Self {
+            remote: MrRemote::new(remote.addr, 8, remote.rkey),
+            tid,
+            lease: None,
+            held: 0,
+            mr,
+            buf,
+        })
+    }
+
+    /// Use leases of the given length, so that the lock can be taken over
+    /// once its holder fails to renew its lease in time.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the ID of this end does not fit in 16 bits.
+    pub fn with_lease(mut self, lease: Duration) -> Self {
+        assert!(
+            self.tid >> (64 - DEADLINE_BITS) == 0,
+            "lock holder ID {} does not fit in 16 bits",
+            self.tid
+        );
+        self.lease = Some(lease);
+        self
+    }
+
+    /// Get the remote lock word.
+    #[inline]
+    pub fn remote(&self) -> &MrRemote {
+        &self.remote
+    }
+
+    /// Get the ID of this end.
+    #[inline]
+    pub fn tid(&self) -> u64 {
+        self.tid
+    }
+
+    /// Return `true` if this end holds the lock, as far as it knows. The lock
+    /// may have been taken over if the lease has expired.
+    #[inline]
+    pub fn is_held(&self) -> bool {
+        self.held != 0
+    }
+
+    /// Try to acquire the lock once, taking it over if its lease has expired.
+    /// Return `true` if the lock is acquired.
+    ///
+    /// # Panics
+    ///
+    /// Panic if this end already holds the lock.
+    pub fn try_lock(&mut self, qp: &Qp) -> io::Result<bool> {
+        assert!(!self.is_held(), "lock is already held by this end");
+
+        let word = self.make_word();
+        let observed = self.cas(qp, 0, word)?;
+        if observed == 0 {
+            self.held = word;
+            return Ok(true);
+        }
+
+        if self.is_expired(observed) {
+            log::warn!(
+                "taking over remote lock from holder {} with expired lease",
+                observed >> DEADLINE_BITS
+            );
+            if self.cas(qp, observed, word)? == observed {
+                self.held = word;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Acquire the lock, retrying with exponential backoff until it is free.
+    ///
+    /// # Panics
+    ///
+    /// Panic if this end already holds the lock.
+    pub fn lock(&mut self, qp: &Qp) -> io::Result<()> {
+        let mut round = 0;
+        while !self.try_lock(qp)? {
+            if round < SPIN_ROUNDS {
+                for _ in 0..1 << round {
+                    hint::spin_loop();
+                }
+                round += 1;
+            } else {
+                thread::yield_now();
+            }
+        }
+        Ok(())
+    }
+
+    /// Release the lock.
+    ///
+    /// Fail with `TimedOut` if the lease has expired and the lock has been
+    /// taken over by another end, in which case the critical section may
+    /// have overlapped with the new holder's. If the compare-and-swap itself
+    /// fails, this end is still considered to hold the lock, and unlocking
+    /// can be retried.
+    ///
+    /// # Panics
+    ///
+    /// Panic if this end does not hold the lock.
+    pub fn unlock(&mut self, qp: &Qp) -> io::Result<()> {
+        let word = self.held;
+        assert_ne!(word, 0, "lock is not held by this end");
+
+        let observed = self.cas(qp, word, 0)?;
+        self.held = 0;
+        if observed != word {
+            return Err(IoError::new(
+                IoErrorKind::TimedOut,
+                "lock lease expired and the lock was taken over",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Extend the lease of the held lock to the full lease length from now.
+    ///
+    /// Fail with `TimedOut` if the lease has already expired and the lock has
+    /// been taken over by another end, in which case this end no longer
+    /// holds the lock.
+    ///
+    /// # Panics
+    ///
+    /// Panic if leases are not used, or if this end does not hold the lock.
+    pub fn renew(&mut self, qp: &Qp) -> io::Result<()> {
+        assert!(self.lease.is_some(), "lock does not use leases");
+        let word = self.held;
+        assert_ne!(word, 0, "lock is not held by this end");
+
+        let renewed = self.make_word();
+        if self.cas(qp, word, renewed)? != word {
+            self.held = 0;
+            return Err(IoError::new(
+                IoErrorKind::TimedOut,
+                "lock lease expired and the lock was taken over",
+            ));
+        }
+        self.held = renewed;
+        Ok(())
+    }
+
+    /// Get the current time in milliseconds since the UNIX epoch, truncated
+    /// to the deadline bits.
+    #[inline]
+    fn now_ms() -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        now.as_millis() as u64 & DEADLINE_MASK
+    }
+
+    /// Make the lock word that this end writes to acquire the lock.
+    fn make_word(&self) -> u64 {
+        match self.lease {
+            Some(lease) => {
+                let deadline = (Self::now_ms() + lease.as_millis() as u64) & DEADLINE_MASK;
+                (self.tid << DEADLINE_BITS) | deadline
+            }
+            None => self.tid,
+        }
+    }
+
+    /// Return `true` if the given lock word carries an expired lease.
+    #[inline]
+    fn is_expired(&self, word: u64) -> bool {
+        self.lease.is_some() && word & DEADLINE_MASK < Self::now_ms()
+    }
+
+    /// Compare-and-swap the lock word, wait for the completion, and return
+    /// the original value.
+    fn cas(&self, qp: &Qp, current: u64, new: u64) -> io::Result<u64> {
+        let scratch = self.mr.slice(0, 8).unwrap();
+        qp.compare_swap_fetch(&scratch, self.remote, current, new, FETCH_WR_ID)?;
+        wait_fetched(qp, &self.buf)
+    }
+}
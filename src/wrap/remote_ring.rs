@@ -0,0 +1,310 @@
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::ops::Range;
+use std::{mem, ptr};
+
+use super::AlignedBuf;
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+
+/// Size of the length header of each ring entry.
+const HEADER_LEN: usize = mem::size_of::<u32>();
+
+/// A single-producer, single-consumer ring buffer that the producer fills
+/// with one-sided RDMA writes into the consumer's memory.
+///
+/// Both ends create a ring with the same number of slots and slot size, which
+/// lays out the same registered buffer on each side: the ring slots, a head
+/// counter, and a tail counter, each isolated in its own cache lines so that
+/// NIC writes to one never invalidate the CPU's cached copy of another. Each
+/// slot holds one entry, prefixed with its length.
+///
+/// - [`RemoteRing::produce()`] stages an entry in the local copy of its slot,
+///   RDMA-writes it into the consumer's slot, and then advances the
+///   consumer's head counter with an RDMA write with immediate, whose receive
+///   completion notifies the consumer. RC ordering guarantees that the entry
+///   lands before the notification.
+/// - [`RemoteRing::consume()`] picks up the notifications and returns the
+///   oldest unconsumed entry. The entry is released on the next call, which
+///   RDMA-writes the advanced tail counter back into the producer's buffer so
+///   that the slot can be reused.
+///
+/// To set up a ring, create it with [`RemoteRing::new()`] on both ends,
+/// exchange [`RemoteRing::ring_remote()`] by whatever means, and hand the
+/// peer's handle over with [`RemoteRing::connect()`].
+///
+/// **NOTE:** the ring polls the send and receive CQs of the QP, which must
+/// therefore not be shared with other QPs.
+pub struct RemoteRing {
+    /// The connected RC QP.
+    qp: Qp,
+
+    /// The memory region of `buf`, dropped first.
+    mr: Mr,
+
+    /// The ring slots, head counter, and tail counter.
+    buf: AlignedBuf,
+
+    /// Byte ranges of the ring slots, the head counter, and the tail counter
+    /// within `buf`.
+    ring: Range<usize>,
+    head_flag: Range<usize>,
+    tail_flag: Range<usize>,
+
+    /// The peer's buffer.
+    remote: Option<MrRemote>,
+
+    /// Number of slots.
+    slots: usize,
+
+    /// Size of one slot, including the length header.
+    slot_size: usize,
+
+    /// Number of entries produced (on the producer) or notified (on the
+    /// consumer).
+    head: u64,
+
+    /// Number of entries consumed.
+    tail: u64,
+
+    /// Whether the entry at `tail` has been returned by `consume` and is yet
+    /// to be released.
+    borrowed: bool,
+}
+
+impl RemoteRing {
+    /// Create one end of a ring on a connected RC QP, with the given number of
+    /// slots of the given size, including a 4-byte length header each.
+    ///
+    /// `slots` zero-length receive requests are posted immediately to take
+    /// the notifications.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the QP is not RC.
+    /// - Panic if `slots` is zero, or exceeds the receive queue depth of the
+    ///   QP.
+    /// - Panic if `slot_size` cannot hold the length header and at least one
+    ///   byte.
+    pub fn new(qp: Qp, slots: usize, slot_size: usize) -> io::Result<Self> {
+        assert_eq!(qp.qp_type(), QpType::Rc, "QP is not RC");
+        assert!(slots > 0, "slot count must be non-zero");
+        assert!(
+            slots <= qp.caps().max_recv_wr as usize,
+            "{} slots exceed the receive queue depth {}",
+            slots,
+            qp.caps().max_recv_wr
+        );
+        assert!(
+            slot_size > HEADER_LEN && slot_size - HEADER_LEN <= u32::MAX as usize,
+            "slot size {} out of range",
+            slot_size
+        );
+
+        let (mut buf, ranges) = AlignedBuf::new_isolated(&[
+            slots * slot_size,
+            mem::size_of::<u64>(),
+            mem::size_of::<u64>(),
+        ])?;
+        // SAFETY: the buffer outlives the MR, which is dropped first.
+        let mr = unsafe { qp.pd().reg(buf.as_mut_ptr(), buf.len())? };
+        let [ring, head_flag, tail_flag]: [Range<usize>; 3] = ranges.try_into().unwrap();
+
+        for slot in 0..slots {
+            qp.recv(&[], slot as _)?;
+        }
+        Ok(Self {
+            qp,
+            mr,
+            buf,
+            ring,
+            head_flag,
+            tail_flag,
+            remote: None,
+            slots,
+            slot_size,
+            head: 0,
+            tail: 0,
+            borrowed: false,
+        })
+    }
+
+    /// Get the underlying QP.
+    #[inline]
+    pub fn qp(&self) -> &Qp {
+        &self.qp
+    }
+
+    /// Get the number of slots.
+    #[inline]
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Get the maximum length of an entry, i.e., the slot size minus the
+    /// length header.
+    #[inline]
+    pub fn max_entry_len(&self) -> usize {
+        self.slot_size - HEADER_LEN
+    }
+
+    /// Get the remote memory handle of the local buffer, which should be sent
+    /// to the other end of the ring.
+    #[inline]
+    pub fn ring_remote(&self) -> MrRemote {
+        self.mr.as_remote()
+    }
+
+    /// Provide the remote memory handle of the other end's buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the remote buffer is too small, i.e., created with a
+    /// different slot count or size.
+    pub fn connect(&mut self, remote: MrRemote) {
+        assert!(
+            remote.len >= self.buf.len(),
+            "remote ring buffer is too small"
+        );
+        self.remote = Some(remote);
+    }
+
+    /// Append an entry to the consumer's ring, blocking until the writes
+    /// complete.
+    ///
+    /// Fail with `WouldBlock` if the ring is full, i.e., the consumer has
+    /// not released enough entries yet, and with `InvalidInput` if the entry
+    /// is longer than [`RemoteRing::max_entry_len()`]. If posting the writes
+    /// fails, the entry is not appended and can be produced again.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the ring is not connected yet.
+    pub fn produce(&mut self, data: &[u8]) -> io::Result<()> {
+        let remote = self.remote.expect("ring is not connected");
+        if data.len() > self.max_entry_len() {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                format!(
+                    "entry length {} exceeds the limit {}",
+                    data.len(),
+                    self.max_entry_len()
+                ),
+            ));
+        }
+        if self.head - self.read_flag(&self.tail_flag) >= self.slots as u64 {
+            return Err(IoError::new(IoErrorKind::WouldBlock, "ring is full"));
+        }
+
+        // Stage the entry in the local copy of its slot.
+        let start = self.slot_start(self.head);
+        let entry_len = HEADER_LEN + data.len();
+        self.buf[start..start + HEADER_LEN].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        self.buf[start + HEADER_LEN..start + entry_len].copy_from_slice(data);
+
+        // Only advance the head once the writes are posted.
+        let head = self.head + 1;
+        self.write_flag(self.head_flag.start, head);
+
+        let entry = self.mr.slice(start, entry_len).unwrap();
+        let flag = self.mr.slice_by_range(self.head_flag.clone()).unwrap();
+        let remote_entry = MrRemote::new(remote.at(start), entry_len, remote.rkey);
+        let remote_flag = MrRemote::new(
+            remote.at(self.head_flag.start),
+            self.head_flag.len(),
+            remote.rkey,
+        );
+        self.qp.write(&[entry], &remote_entry, 0, None, false)?;
+        self.qp
+            .write(&[flag], &remote_flag, 0, Some(head as _), true)?;
+        self.head = head;
+        self.wait_send()
+    }
+
+    /// Release the previously consumed entry, if any, and return the oldest
+    /// unconsumed one, or `None` if the ring is empty. Do not block.
+    ///
+    /// Fail with `InvalidData` if the length in the header of the entry
+    /// exceeds [`RemoteRing::max_entry_len()`], e.g., if the producer uses a
+    /// different slot size.
+    ///
+    /// # Panics
+    ///
+    /// Panic if the ring is not connected yet.
+    pub fn consume(&mut self) -> io::Result<Option<&[u8]>> {
+        let remote = self.remote.expect("ring is not connected");
+        if self.borrowed {
+            self.borrowed = false;
+            self.tail += 1;
+            self.write_flag(self.tail_flag.start, self.tail);
+
+            let flag = self.mr.slice_by_range(self.tail_flag.clone()).unwrap();
+            let remote_flag = MrRemote::new(
+                remote.at(self.tail_flag.start),
+                self.tail_flag.len(),
+                remote.rkey,
+            );
+            self.qp.write(&[flag], &remote_flag, 0, None, true)?;
+            self.wait_send()?;
+        }
+
+        // Every notification stands for one entry, and is only delivered
+        // after the entry has been written.
+        while let Some(wc) = self.qp.rcq().poll_one()? {
+            self.qp.account_recv(&wc);
+            wc.ok().map_err(IoError::from)?;
+            self.qp.recv(&[], wc.wr_id())?;
+            self.head += 1;
+        }
+        if self.head == self.tail {
+            return Ok(None);
+        }
+
+        let start = self.slot_start(self.tail);
+        let mut len = [0u8; HEADER_LEN];
+        len.copy_from_slice(&self.buf[start..start + HEADER_LEN]);
+        let len = u32::from_le_bytes(len) as usize;
+        if len > self.max_entry_len() {
+            return Err(IoError::new(
+                IoErrorKind::InvalidData,
+                format!(
+                    "entry length {} exceeds the limit {}",
+                    len,
+                    self.max_entry_len()
+                ),
+            ));
+        }
+
+        self.borrowed = true;
+        Ok(Some(
+            &self.buf[start + HEADER_LEN..start + HEADER_LEN + len],
+        ))
+    }
+
+    /// Get the offset of the slot of the given entry within the buffer.
+    #[inline]
+    fn slot_start(&self, index: u64) -> usize {
+        self.ring.start + (index % self.slots as u64) as usize * self.slot_size
+    }
+
+    /// Read a counter written by the other end.
+    #[inline]
+    fn read_flag(&self, flag: &Range<usize>) -> u64 {
+        // SAFETY: the counter is within the buffer and cache-line aligned.
+        unsafe { ptr::read_volatile(self.buf.addr().add(flag.start) as *const u64) }
+    }
+
+    /// Stage a counter value to be written to the other end.
+    #[inline]
+    fn write_flag(&mut self, offset: usize, value: u64) {
+        // SAFETY: the counter is within the buffer and cache-line aligned.
+        unsafe { ptr::write_volatile(self.buf.addr().add(offset) as *mut u64, value) };
+    }
+
+    /// Wait for the completion of the last signaled write.
+    fn wait_send(&self) -> io::Result<()> {
+        let wc = self.qp.scq().poll_one_blocking()?;
+        self.qp.account_send(&wc);
+        wc.ok().map_err(IoError::from)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use std::hint;
+use std::io::{self, Error as IoError};
+
+use crate::rdma::cq::Wc;
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+use crate::rdma::type_alias::WrId;
+use crate::rdma::wr::*;
+
+/// Writes the same local buffer to several remote regions on one connected
+/// QP, e.g., to append an entry to a replicated log.
+///
+/// The RDMA writes to all targets are linked into a single work request chain
+/// and posted with one `ibv_post_send` call, i.e., one doorbell. As RC and UC
+/// QPs execute send work requests in order, only the last write is signaled,
+/// and its completion indicates that all writes have completed.
+///
+/// The work requests are built once on creation and reused across rounds, so
+/// replicating does not allocate.
+///
+/// [`Replicator::wait()`] polls the send CQ of the QP for the completions of
+/// the round by QP number and work request ID, deferring other completions
+/// like [`Cq::poll_matching()`](crate::rdma::cq::Cq::poll_matching) does.
+pub struct Replicator<'a> {
+    /// The connected QP to post writes to.
+    qp: &'a Qp,
+
+    /// One write work request per target.
+    wrs: Vec<SendWr<'a, 1>>,
+
+    /// Length of the shortest target.
+    min_len: usize,
+
+    /// Work request ID of the round posted and not yet waited for, if any.
+    in_flight: Option<WrId>,
+}
+
+impl<'a> Replicator<'a> {
+    /// Create a replicator that writes to the given remote regions through the
+    /// given QP.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the QP is neither RC nor UC.
+    /// - Panic if `targets` is empty.
+    pub fn new(qp: &'a Qp, targets: &[MrRemote]) -> Self {
+        assert!(
+            matches!(qp.qp_type(), QpType::Rc | QpType::Uc),
+            "QP is neither RC nor UC"
+        );
+        assert!(!targets.is_empty(), "no replication targets");
+
+        let wrs = targets
+            .iter()
+            .map(|target| {
+                let mut wr = SendWr::default();
+                wr.set_wr_write(*target, None);
+                wr
+            })
+            .collect();
+        let min_len = targets.iter().map(|target| target.len).min().unwrap();
+        Self {
+            qp,
+            wrs,
+            min_len,
+            in_flight: None,
+        }
+    }
+
+    /// Get the number of targets.
+    #[inline]
+    pub fn num_targets(&self) -> usize {
+        self.wrs.len()
+    }
+
+    /// Post writes of `local` to all targets as a single chain. The last write
+    /// is signaled with the given work request ID.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if `local` is longer than any of the targets.
+    /// - Panic if the previous round has not been waited for.
+    pub fn post(&mut self, local: &MrSlice<'a>, wr_id: WrId) -> io::Result<()> {
+        assert!(
+            self.in_flight.is_none(),
+            "previous round not yet waited for"
+        );
+        assert!(
+            local.len() <= self.min_len,
+            "local buffer ({} bytes) exceeds the shortest target ({} bytes)",
+            local.len(),
+            self.min_len
+        );
+
+        let last = self.wrs.len() - 1;
+        let mut chain = SendWrChain::new();
+        for (i, wr) in self.wrs.iter_mut().enumerate() {
+            wr.set_sge(0, local).set_id(wr_id);
+            wr.set_flags(0);
+            if i == last {
+                wr.set_flag_signaled();
+            }
+            chain.push(wr);
+        }
+        chain.post_on(self.qp)?;
+        self.in_flight = Some(wr_id);
+        Ok(())
+    }
+
+    /// Block until the writes posted by the last [`Replicator::post()`] have
+    /// completed on all targets.
+    ///
+    /// If the QP signals all sends, this consumes one completion per target.
+    /// Fail with the status of the first failed write, if any.
+    pub fn wait(&mut self) -> io::Result<()> {
+        let wr_id = match self.in_flight.take() {
+            Some(wr_id) => wr_id,
+            None => return Ok(()),
+        };
+
+        let num = if self.qp.sq_sig_all() {
+            self.wrs.len()
+        } else {
+            1
+        };
+        let qp_num = self.qp.qp_num();
+        let is_round = |wc: &Wc| wc.qp_num() == qp_num && wc.wr_id() == wr_id;
+
+        let mut ret = Ok(());
+        let mut wc = [Wc::default()];
+        for _ in 0..num {
+            while self.qp.scq().poll_matching(is_round, &mut wc)?.0 == 0 {
+                hint::spin_loop();
+            }
+            self.qp.account_send(&wc[0]);
+            if ret.is_ok() {
+                ret = wc[0].ok().map(|_| ()).map_err(IoError::from);
+            }
+        }
+        ret
+    }
+
+    /// Write `local` to all targets and block until all writes complete.
+    /// See [`Replicator::post()`] and [`Replicator::wait()`] for details.
+    pub fn replicate(&mut self, local: &MrSlice<'a>, wr_id: WrId) -> io::Result<()> {
+        self.post(local, wr_id)?;
+        self.wait()
+    }
+}
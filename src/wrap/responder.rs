@@ -0,0 +1,155 @@
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+
+use super::RegisteredMem;
+use crate::rdma::cq::Wc;
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+
+/// The receiving end of an event-driven RPC server, which sleeps on the
+/// completion channel of its receive CQ between requests instead of
+/// busy-polling.
+///
+/// The responder keeps a ring of receive buffers ("slots") posted to the QP.
+/// When no request is pending, it arms the receive CQ for *solicited*
+/// completions only and blocks on the completion channel. Senders must
+/// therefore post their requests with the `IBV_SEND_SOLICITED` flag, e.g.,
+/// with [`SendWr::set_flag_solicited()`](crate::rdma::wr::SendWr::set_flag_solicited);
+/// unsolicited messages are still received, but do not wake the responder up
+/// until the next solicited one arrives.
+///
+/// Replies are sent through [`Responder::qp()`] as usual.
+///
+/// **NOTE:** the responder polls the receive CQ of the QP, which must be
+/// created with [`Cq::new_notifiable()`](crate::rdma::cq::Cq::new_notifiable)
+/// and not shared with other QPs.
+pub struct Responder {
+    /// The connected QP.
+    qp: Qp,
+
+    /// Receive buffers, `slots * slot_size` long.
+    recv_bufs: RegisteredMem,
+
+    /// Size of one slot.
+    slot_size: usize,
+}
+
+impl Responder {
+    /// Create a responder on a connected QP, with the given number of receive
+    /// slots of the given size. All slots are posted immediately.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the receive CQ of the QP has no completion channel.
+    /// - Panic if `slots` or `slot_size` is zero, or if `slots` exceeds the
+    ///   receive queue depth of the QP.
+    pub fn new(qp: Qp, slots: usize, slot_size: usize) -> io::Result<Self> {
+        assert!(
+            qp.rcq().comp_channel().is_some(),
+            "receive CQ has no completion channel; create it with `Cq::new_notifiable`"
+        );
+        assert!(
+            slots > 0 && slot_size > 0,
+            "slot count and size must be non-zero"
+        );
+        assert!(
+            slots <= qp.caps().max_recv_wr as usize,
+            "{} slots exceed the receive queue depth {}",
+            slots,
+            qp.caps().max_recv_wr
+        );
+
+        let recv_bufs = RegisteredMem::new(qp.pd(), slots * slot_size)?;
+        let this = Self {
+            qp,
+            recv_bufs,
+            slot_size,
+        };
+        for slot in 0..slots {
+            this.post_slot(slot)?;
+        }
+        Ok(this)
+    }
+
+    /// Get the underlying QP.
+    #[inline]
+    pub fn qp(&self) -> &Qp {
+        &self.qp
+    }
+
+    /// Get the slot size.
+    #[inline]
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    /// Block until a request arrives, sleeping on the completion channel while
+    /// none is pending. Call `f` with the receive completion and the received
+    /// data, re-post the slot, and return the result of `f`.
+    ///
+    /// Fail with the completion status if the receive failed, in which case
+    /// `f` is not called. The slot is re-posted in any case, also if `f`
+    /// panics, so that failed requests and replies do not shrink the ring.
+    pub fn recv_with<R>(&mut self, f: impl FnOnce(&Wc, &[u8]) -> R) -> io::Result<R> {
+        let wc = self.wait_recv()?;
+        self.qp.account_recv(&wc);
+        let slot = wc.wr_id() as usize;
+        let ret = wc.ok().map(|len| {
+            let start = slot * self.slot_size;
+            let data = &self.recv_bufs[start..start + len];
+            panic::catch_unwind(AssertUnwindSafe(|| f(&wc, data)))
+        });
+
+        let reposted = self.post_slot(slot);
+        match ret {
+            Ok(Ok(ret)) => reposted.map(|_| ret),
+            Ok(Err(payload)) => {
+                if let Err(e) = reposted {
+                    log::warn!("cannot re-post receive slot {}: {}", slot, e);
+                }
+                panic::resume_unwind(payload)
+            }
+            Err(status) => {
+                if let Err(e) = reposted {
+                    log::warn!("cannot re-post receive slot {}: {}", slot, e);
+                }
+                Err(status.into())
+            }
+        }
+    }
+
+    /// Block until a request arrives and return a copy of its data.
+    /// See [`Responder::recv_with()`] for details.
+    pub fn recv(&mut self) -> io::Result<Vec<u8>> {
+        self.recv_with(|_, data| data.to_vec())
+    }
+
+    /// Poll the receive CQ for one completion, sleeping on the completion
+    /// channel until a solicited completion arrives if there is none.
+    fn wait_recv(&self) -> io::Result<Wc> {
+        let cq = self.qp.rcq();
+        loop {
+            if let Some(wc) = cq.poll_one()? {
+                return Ok(wc);
+            }
+
+            // Completions that arrived before arming do not generate events,
+            // so poll once more before sleeping.
+            cq.req_notify(true)?;
+            if let Some(wc) = cq.poll_one()? {
+                return Ok(wc);
+            }
+            cq.wait_event()?;
+        }
+    }
+
+    /// Post the receive buffer of the given slot, using the slot index as the
+    /// work request ID.
+    fn post_slot(&self, slot: usize) -> io::Result<()> {
+        let buf = self
+            .recv_bufs
+            .slice(slot * self.slot_size, self.slot_size)
+            .unwrap();
+        self.qp.recv(&[buf], slot as _)
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::{self, Error as IoError, ErrorKind as IoErrorKind};
+use std::ops::Range;
+
+use crate::rdma::mr::*;
+use crate::rdma::qp::*;
+use crate::rdma::type_alias::WrId;
+
+/// A scatter-gather list of non-contiguous local buffers, split into work
+/// requests that respect the SGE limit of a QP.
+///
+/// This maps vectored application buffers, e.g., a set of `IoSlice`s, onto
+/// RDMA scatter-gather lists. Every entry is validated against the bounds of
+/// its memory region on insertion. A work request carrying more SGEs than the
+/// QP's `max_send_sge` fails with `EINVAL`; the builder therefore groups the
+/// entries into batches of at most that many SGEs, one work request each.
+#[derive(Debug, Clone)]
+pub struct ScatterGather<'a> {
+    /// Validated scatter-gather entries, in order.
+    slices: Vec<MrSlice<'a>>,
+
+    /// Maximum number of SGEs in one work request.
+    max_sge: usize,
+}
+
+impl<'a> ScatterGather<'a> {
+    /// Create an empty scatter-gather list that puts at most `max_sge` entries
+    /// in one work request.
+    ///
+    /// # Panics
+    ///
+    /// Panic if `max_sge` is zero.
+    pub fn new(max_sge: usize) -> Self {
+        assert!(max_sge > 0, "SGE limit must be non-zero");
+        Self {
+            slices: Vec::new(),
+            max_sge,
+        }
+    }
+
+    /// Create an empty scatter-gather list for send work requests on the
+    /// given QP, i.e., limited by its `max_send_sge`.
+    pub fn for_qp(qp: &Qp) -> Self {
+        Self::new(qp.caps().max_send_sge as _)
+    }
+
+    /// Create a scatter-gather list from `(MR, range)` pairs, limited by
+    /// `max_sge` entries per work request.
+    pub fn from_ranges(
+        max_sge: usize,
+        ranges: impl IntoIterator<Item = (&'a Mr, Range<usize>)>,
+    ) -> io::Result<Self> {
+        let mut this = Self::new(max_sge);
+        for (mr, range) in ranges {
+            this.push(mr, range)?;
+        }
+        Ok(this)
+    }
+
+    /// Append the given range of the given MR to the list.
+    ///
+    /// Empty ranges are skipped, as they carry no data. Fail with
+    /// `InvalidInput` if the range is out of the bounds of the MR, or is too
+    /// long to fit in one SGE.
+    pub fn push(&mut self, mr: &'a Mr, range: Range<usize>) -> io::Result<&mut Self> {
+        if range.is_empty() {
+            return Ok(self);
+        }
+        if range.len() > u32::MAX as usize {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                format!("range length {} exceeds the SGE limit", range.len()),
+            ));
+        }
+
+        let slice = mr.slice_by_range(range.clone()).ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::InvalidInput,
+                format!("range {:?} out of MR bounds (len {})", range, mr.len()),
+            )
+        })?;
+        self.slices.push(slice);
+        Ok(self)
+    }
+
+    /// Get the number of entries in the list.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slices.len()
+    }
+
+    /// Return `true` if the list has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    /// Get the total number of bytes in the list.
+    pub fn total_len(&self) -> usize {
+        self.slices.iter().map(|s| s.len()).sum()
+    }
+
+    /// Get the maximum number of entries in one work request.
+    #[inline]
+    pub fn max_sge(&self) -> usize {
+        self.max_sge
+    }
+
+    /// Get the number of work requests needed to carry the list.
+    #[inline]
+    pub fn num_wrs(&self) -> usize {
+        self.slices.len().div_ceil(self.max_sge)
+    }
+
+    /// Get all entries of the list.
+    #[inline]
+    pub fn slices(&self) -> &[MrSlice<'a>] {
+        &self.slices
+    }
+
+    /// Consume the list and return its entries.
+    #[inline]
+    pub fn into_vec(self) -> Vec<MrSlice<'a>> {
+        self.slices
+    }
+
+    /// Get an iterator over the batches of entries, one per work request.
+    /// Each batch is yielded together with its byte offset within the list,
+    /// i.e., the total length of all preceding batches.
+    pub fn batches(&self) -> impl Iterator<Item = (usize, &[MrSlice<'a>])> {
+        let mut offset = 0;
+        self.slices.chunks(self.max_sge).map(move |batch| {
+            let this_offset = offset;
+            offset += batch.iter().map(|s| s.len()).sum::<usize>();
+            (this_offset, batch)
+        })
+    }
+
+    /// Post RDMA writes that gather the list into the given contiguous remote
+    /// region, one write per batch. Only the last write is signaled if
+    /// `signal` is `true`, and its completion indicates that all writes have
+    /// completed. Return the number of posted work requests.
+    ///
+    /// **NOTE:** this function is only equivalent to calling `ibv_post_send`
+    /// once per batch. If a post fails, the preceding writes are still
+    /// executed.
+    ///
+    /// # Panics
+    ///
+    /// - Panic if the QP is neither RC nor UC.
+    /// - Panic if the list is longer than `remote`.
+    pub fn write_to(
+        &self,
+        qp: &Qp,
+        remote: &MrRemote,
+        wr_id: WrId,
+        signal: bool,
+    ) -> io::Result<usize> {
+        let total_len = self.total_len();
+        assert!(
+            total_len <= remote.len,
+            "scatter-gather list ({} bytes) exceeds the remote region ({} bytes)",
+            total_len,
+            remote.len
+        );
+
+        let num_wrs = self.num_wrs();
+        for (i, (offset, batch)) in self.batches().enumerate() {
+            let len = batch.iter().map(|s| s.len()).sum::<usize>();
+            let target = MrRemote::new(remote.at(offset), len, remote.rkey);
+            qp.write(batch, &target, wr_id, None, signal && i == num_wrs - 1)?;
+        }
+        Ok(num_wrs)
+    }
+}
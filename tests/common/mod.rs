@@ -0,0 +1,17 @@
+//! Fixtures shared by the tests that run on an RDMA device.
+
+use rrddmma::prelude::*;
+
+/// Create an RC QP with default capabilities on the given CQ, and bind it to
+/// the given port with its recommended GID.
+pub fn make_qp(pd: &Pd, cq: &Cq, port: &Port) -> anyhow::Result<Qp> {
+    let mut qp = Qp::builder()
+        .qp_type(QpType::Rc)
+        .caps(QpCaps::default())
+        .send_cq(cq)
+        .recv_cq(cq)
+        .sq_sig_all(false)
+        .build(pd)?;
+    qp.bind_local_port(port, None)?;
+    Ok(qp)
+}
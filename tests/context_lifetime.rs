@@ -0,0 +1,30 @@
+//! Resources must keep their device context alive: PDs, CQs, QPs and memory
+//! regions stay usable after the application drops its `Context` and ports.
+
+mod common;
+
+use common::make_qp;
+use rrddmma::{ctrl::Connecter, prelude::*, wrap::RegisteredMem};
+
+#[test]
+#[ignore = "requires an RDMA device"]
+fn resources_outlive_context_handle() -> anyhow::Result<()> {
+    let Nic { context, ports } = Nic::finder().probe()?;
+    let pd = Pd::new(&context)?;
+    let cq = Cq::new(&context, Cq::DEFAULT_CQ_DEPTH)?;
+    let mut first = make_qp(&pd, &cq, &ports[0])?;
+    let mut second = make_qp(&pd, &cq, &ports[0])?;
+
+    // Drop every handle of the context that the application owns.
+    drop(context);
+    drop(ports);
+
+    let src = RegisteredMem::new_with_content(&pd, b"Hello, rrddmma!")?;
+    let dst = RegisteredMem::new(&pd, src.len())?;
+    Connecter::connect_local(&mut first, &mut second)?;
+
+    first.write(&[src.as_slice()], &dst.mr().as_remote(), 0, None, true)?;
+    cq.poll_one_blocking()?.ok()?;
+    assert_eq!(&dst[..], &src[..]);
+    Ok(())
+}
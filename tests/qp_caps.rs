@@ -0,0 +1,61 @@
+//! `Qp::caps()` must report the capabilities granted by the device, which
+//! may exceed the requested ones, and the send queue must hold as many work
+//! requests as reported.
+
+use rrddmma::{ctrl::Connecter, prelude::*};
+
+#[test]
+#[ignore = "requires an RDMA device"]
+fn caps_reflect_granted_values() -> anyhow::Result<()> {
+    let Nic { context, ports } = Nic::finder().probe()?;
+    let pd = Pd::new(&context)?;
+    let cq = Cq::new(&context, Cq::DEFAULT_CQ_DEPTH)?;
+
+    // Odd values, which devices commonly round up.
+    let requested = QpCaps {
+        max_send_wr: 100,
+        max_recv_wr: 100,
+        max_send_sge: 3,
+        max_recv_sge: 3,
+        max_inline_data: 100,
+    };
+    let build = || {
+        Qp::builder()
+            .qp_type(QpType::Rc)
+            .caps(requested)
+            .send_cq(&cq)
+            .recv_cq(&cq)
+            .sq_sig_all(false)
+            .build_with_report(&pd)
+    };
+    let (mut first, report) = build()?;
+    let (mut second, _) = build()?;
+
+    let caps = *first.caps();
+    assert_eq!(caps.max_send_wr, report.granted.max_send_wr);
+    assert_eq!(caps.max_recv_wr, report.granted.max_recv_wr);
+    assert_eq!(caps.max_send_sge, report.granted.max_send_sge);
+    assert_eq!(caps.max_recv_sge, report.granted.max_recv_sge);
+    assert_eq!(caps.max_inline_data, report.granted.max_inline_data);
+    assert_eq!(first.inline_capacity(), caps.max_inline_data);
+
+    assert!(caps.max_send_wr >= requested.max_send_wr);
+    assert!(caps.max_recv_wr >= requested.max_recv_wr);
+    assert!(caps.max_send_sge >= requested.max_send_sge);
+    assert!(caps.max_recv_sge >= requested.max_recv_sge);
+    let inline = report
+        .inline_downgraded_to
+        .unwrap_or(requested.max_inline_data);
+    assert!(caps.max_inline_data >= inline);
+
+    // A send queue sized off the granted capabilities must not overflow.
+    first.bind_local_port(&ports[0], None)?;
+    second.bind_local_port(&ports[0], None)?;
+    Connecter::connect_local(&mut first, &mut second)?;
+    for i in 0..caps.max_send_wr {
+        let signal = i == caps.max_send_wr - 1;
+        first.write(&[], &MrRemote::dummy(), i as _, None, signal)?;
+    }
+    cq.poll_one_blocking()?.ok()?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! A reset QP must be reusable for a new connection, keeping its QP number
+//! and capabilities.
+
+mod common;
+
+use common::make_qp;
+use rrddmma::{ctrl::Connecter, prelude::*, wrap::RegisteredMem};
+
+/// Write `src` through `qp` into `dst`, and check that it arrives.
+fn write_through(qp: &Qp, src: &RegisteredMem, dst: &RegisteredMem) -> anyhow::Result<()> {
+    qp.write(&[src.as_slice()], &dst.mr().as_remote(), 0, None, true)?;
+    qp.scq().poll_one_blocking()?.ok()?;
+    assert_eq!(&dst[..], &src[..]);
+    Ok(())
+}
+
+#[test]
+#[ignore = "requires an RDMA device"]
+fn reuse_after_reset() -> anyhow::Result<()> {
+    let Nic { context, ports } = Nic::finder().probe()?;
+    let pd = Pd::new(&context)?;
+    let cq = Cq::new(&context, Cq::DEFAULT_CQ_DEPTH)?;
+    let port = &ports[0];
+
+    let mut qp = make_qp(&pd, &cq, port)?;
+    let qp_num = qp.qp_num();
+    let caps = *qp.caps();
+
+    let src = RegisteredMem::new_with_content(&pd, b"Hello, rrddmma!")?;
+    let dst = RegisteredMem::new(&pd, src.len())?;
+    let mut first = make_qp(&pd, &cq, port)?;
+    Connecter::connect_local(&mut qp, &mut first)?;
+    write_through(&qp, &src, &dst)?;
+
+    // Recycle the QP for a connection with another peer.
+    qp.reset()?;
+    assert!(qp.is_reusable());
+    assert_eq!(qp.qp_num(), qp_num);
+    assert_eq!(qp.caps().max_send_wr, caps.max_send_wr);
+    assert_eq!(qp.caps().max_recv_wr, caps.max_recv_wr);
+    assert_eq!(qp.caps().max_inline_data, caps.max_inline_data);
+
+    let dst = RegisteredMem::new(&pd, src.len())?;
+    let mut second = make_qp(&pd, &cq, port)?;
+    qp.bind_local_port(port, None)?;
+    Connecter::connect_local(&mut qp, &mut second)?;
+    assert!(!qp.is_reusable());
+    write_through(&qp, &src, &dst)?;
+    Ok(())
+}